@@ -66,10 +66,10 @@ impl State {
         rpass.set_index_buffer(self.index_buffer(), wgpu::IndexFormat::Uint16);
         rpass.draw_indexed(0..self.num_indices(), 0, 0..1);
     }
-    pub fn vertex_buffer(&self) -> wgpu::BufferSlice {
+    pub fn vertex_buffer(&self) -> wgpu::BufferSlice<'_> {
         self.vertex_buffer.slice(..)
     }
-    pub fn index_buffer(&self) -> wgpu::BufferSlice {
+    pub fn index_buffer(&self) -> wgpu::BufferSlice<'_> {
         self.index_buffer.slice(..)
     }
     pub fn num_indices(&self) -> u32 {
@@ -1,7 +1,9 @@
-use app_surface::AppSurface;
-use wgpu::{Queue, SurfaceConfiguration};
-use winit::dpi::PhysicalPosition;
+use wgpu::Queue;
 
+use crate::camera::Camera;
+use crate::gpu::Gpu;
+
+#[allow(clippy::module_inception)]
 mod base_shape;
 mod zoom;
 
@@ -14,21 +16,16 @@ pub struct State {
 impl State {
     pub fn draw<'a, 'b>(&'a mut self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
         rpass.set_pipeline(&self.render_pipeline);
-        rpass.set_bind_group(0, &self.zoom.bind_group(), &[]);
+        rpass.set_bind_group(0, self.zoom.bind_group(), &[]);
 
         self.basic_shape.draw(rpass);
     }
-    pub fn update(&mut self, queue: &Queue) {
-        self.zoom.update_proj();
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        self.zoom.update_proj(camera);
         queue.write_buffer(self.zoom.buffer(), 0, self.zoom.data());
     }
-    pub fn change_zoom(&mut self, config: &SurfaceConfiguration, p1: PhysicalPosition<f64>, p2: PhysicalPosition<f64>) {
-        let offset_x = (p2.x - p1.x) as f32 / config.height as f32 * 2.0;
-        let offset_y = -(p2.y - p1.y) as f32 / config.height as f32 * 2.0;
-        self.zoom.translation(offset_x, offset_y);
-    }
-    pub fn new(app: &AppSurface) -> Self {let zoom = zoom::State::new(&app.device);
-        let basic_shape = base_shape::State::new(&app.device);
+    pub fn new(app: &Gpu) -> Self {let zoom = zoom::State::new(app.device);
+        let basic_shape = base_shape::State::new(app.device);
 
         let render_basic_shape_pipeline_layout =
             app.device
@@ -92,6 +89,6 @@ impl State {
                 // indicates how many array layers the attachments will have.
                 multiview: None,
             });
-        Self { render_pipeline: render_basic_shape_pipeline, basic_shape, zoom: zoom::State::new(&app.device) }
+        Self { render_pipeline: render_basic_shape_pipeline, basic_shape, zoom }
     }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use glam::Mat4;
 use wgpu::util::DeviceExt;
 
+use crate::camera::Camera;
+
 // 此属性标注数据的内存布局兼容 C-ABI，令其可用于着色器
 #[repr(C)]
 // derive 属性自动导入的这些 trait，令其可被存入缓冲区
@@ -18,26 +20,12 @@ impl Uniform {
         }
     }
 
-    pub fn update_proj(&mut self, zoom: &Zoom) {
-        let mut scale = Mat4::IDENTITY.to_cols_array_2d();
-        scale[0][0] = 9.0 / 16.0;
-        let zoom = Mat4::from_cols_array_2d(&scale) * zoom.zoom;
-        self.proj = zoom.to_cols_array_2d();
-    }
-}
-
-pub struct Zoom {
-    zoom: Mat4,
-}
-
-impl Zoom {
-    pub fn new() -> Self {
-        Self { zoom: Mat4::IDENTITY }
+    pub fn update_proj(&mut self, camera: &Camera) {
+        self.proj = camera.projection().to_cols_array_2d();
     }
 }
 
 pub struct State {
-    zoom: Zoom,
     uniform: Uniform,
     buffer: wgpu::Buffer,
     layout: wgpu::BindGroupLayout,
@@ -45,11 +33,8 @@ pub struct State {
 }
 
 impl State {
-    pub fn update_proj(&mut self) {
-        self.uniform.update_proj(&self.zoom);
-    }
-    pub fn translation(&mut self, x: f32, y: f32) {
-        self.zoom.zoom = self.zoom.zoom * Mat4::from_translation(glam::vec3(x, y, 0.0));
+    pub fn update_proj(&mut self, camera: &Camera) {
+        self.uniform.update_proj(camera);
     }
     pub fn layout(&self) -> &wgpu::BindGroupLayout {
         &self.layout
@@ -65,10 +50,7 @@ impl State {
     }
     pub fn new(device: &wgpu::Device) -> Self {
 
-        let zoom = Zoom::new();
-
-        let mut uniform = Uniform::new();
-        uniform.update_proj(&zoom);
+        let uniform = Uniform::new();
 
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -105,7 +87,6 @@ impl State {
         });
 
         Self {
-            zoom,
             uniform,
             buffer, layout, bind_group
         }
@@ -0,0 +1,227 @@
+use glam::{vec2, vec3, Mat4, Vec2};
+
+/// Limits of `Camera::scale`.
+const MIN_SCALE: f32 = 0.05;
+const MAX_SCALE: f32 = 200.0;
+
+/// Where the document is seen from, shared by every layer.
+///
+/// Documents live in world coordinates. The view matrix maps them to view units, in which the window
+/// spans `-1..1` vertically and `-ratio..ratio` horizontally; screen positions are in pixels from
+/// the top left corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    view: Mat4,
+    size: Vec2,
+    scale_factor: f32,
+}
+
+impl Camera {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut camera = Self { view: Mat4::IDENTITY, size: Vec2::ONE, scale_factor: 1.0 };
+        camera.resize(width, height);
+        camera
+    }
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = vec2(width.max(1) as f32, height.max(1) as f32);
+    }
+    /// Physical pixels per logical pixel, as reported by the window.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+    pub fn ratio(&self) -> f32 {
+        self.size.x / self.size.y
+    }
+    /// How much the view is magnified, 1 when nothing is zoomed.
+    pub fn scale(&self) -> f32 {
+        self.view.x_axis.truncate().length()
+    }
+    /// How many pixels one world unit covers.
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.size.y / 2.0 * self.scale()
+    }
+    /// Angle of the view in radians, counterclockwise.
+    pub fn rotation(&self) -> f32 {
+        self.view.x_axis.y.atan2(self.view.x_axis.x)
+    }
+    pub fn view(&self) -> Mat4 {
+        self.view
+    }
+    /// Goes back to a view saved from `view`.
+    pub fn set_view(&mut self, view: Mat4) {
+        self.view = view;
+    }
+    /// Maps world coordinates to clip space for the shaders.
+    pub fn projection(&self) -> Mat4 {
+        Mat4::from_scale(vec3(1.0 / self.ratio(), 1.0, 1.0)) * self.view
+    }
+    pub fn contains(&self, screen: Vec2) -> bool {
+        screen.cmpge(Vec2::ZERO).all() && screen.cmplt(self.size).all()
+    }
+    pub fn screen_to_view(&self, screen: Vec2) -> Vec2 {
+        vec2((screen.x / self.size.x * 2.0 - 1.0) * self.ratio(), 1.0 - screen.y / self.size.y * 2.0)
+    }
+    pub fn view_to_screen(&self, view: Vec2) -> Vec2 {
+        vec2((view.x / self.ratio() + 1.0) / 2.0 * self.size.x, (1.0 - view.y) / 2.0 * self.size.y)
+    }
+    /// The world position under a screen position.
+    pub fn unproject(&self, screen: Vec2) -> Vec2 {
+        self.view.inverse().transform_point3(self.screen_to_view(screen).extend(0.0)).truncate()
+    }
+    /// The world rectangle covering the window, larger than it when the view is rotated.
+    pub fn visible_bounds(&self) -> (Vec2, Vec2) {
+        let corners = [Vec2::ZERO, vec2(self.size.x, 0.0), self.size, vec2(0.0, self.size.y)].map(|c| self.unproject(c));
+        (corners.into_iter().fold(Vec2::INFINITY, Vec2::min), corners.into_iter().fold(Vec2::NEG_INFINITY, Vec2::max))
+    }
+    /// The screen position of a world position.
+    #[allow(dead_code)]
+    pub fn project(&self, world: Vec2) -> Vec2 {
+        self.view_to_screen(self.view.transform_point3(world.extend(0.0)).truncate())
+    }
+    /// Drags the world along with the mouse moving between two screen positions.
+    pub fn pan(&mut self, from: Vec2, to: Vec2) {
+        let offset = self.screen_to_view(to) - self.screen_to_view(from);
+        self.view = Mat4::from_translation(offset.extend(0.0)) * self.view;
+    }
+    /// Magnifies by `factor`, keeping the world position under `screen` in place.
+    pub fn zoom_at(&mut self, screen: Vec2, factor: f32) {
+        let scale = self.scale();
+        let factor = (scale * factor).clamp(MIN_SCALE, MAX_SCALE) / scale;
+        let anchor = self.screen_to_view(screen).extend(0.0);
+        self.view = Mat4::from_translation(anchor)
+            * Mat4::from_scale(vec3(factor, factor, 1.0))
+            * Mat4::from_translation(-anchor)
+            * self.view;
+    }
+    /// Turns the view counterclockwise by `angle` radians around `screen`.
+    pub fn rotate_at(&mut self, screen: Vec2, angle: f32) {
+        let anchor = self.screen_to_view(screen).extend(0.0);
+        self.view = Mat4::from_translation(anchor)
+            * Mat4::from_rotation_z(angle)
+            * Mat4::from_translation(-anchor)
+            * self.view;
+    }
+    /// Centres the world rectangle from `min` to `max`, with a small margin around it, keeping the
+    /// rotation of the view.
+    pub fn fit(&mut self, min: Vec2, max: Vec2) {
+        let rotation = Mat4::from_rotation_z(self.rotation());
+        let center = (min + max) / 2.0;
+        // 旋转后的外接矩形
+        let half = [min, vec2(min.x, max.y), max, vec2(max.x, min.y)].iter()
+            .map(|&corner| rotation.transform_vector3((corner - center).extend(0.0)).truncate().abs())
+            .fold(Vec2::ZERO, Vec2::max);
+        let half = (half * 1.1).max(Vec2::splat(1e-6));
+        let scale = (self.ratio() / half.x).min(1.0 / half.y).clamp(MIN_SCALE, MAX_SCALE);
+        self.view = Mat4::from_scale(vec3(scale, scale, 1.0)) * rotation * Mat4::from_translation(-center.extend(0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn world_and_screen_round_trip() {
+        let mut camera = Camera::new(800, 400);
+        assert_near(camera.unproject(vec2(400.0, 200.0)), Vec2::ZERO);
+        assert_near(camera.unproject(vec2(800.0, 0.0)), vec2(2.0, 1.0));
+        camera.pan(vec2(100.0, 100.0), vec2(300.0, 50.0));
+        camera.zoom_at(vec2(123.0, 321.0), 3.0);
+        let world = vec2(0.3, -0.7);
+        assert_near(camera.unproject(camera.project(world)), world);
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor_and_is_clamped() {
+        let mut camera = Camera::new(800, 400);
+        let anchor = vec2(600.0, 100.0);
+        let before = camera.unproject(anchor);
+        camera.zoom_at(anchor, 4.0);
+        assert_near(camera.unproject(anchor), before);
+        assert_eq!(camera.scale(), 4.0);
+        camera.zoom_at(anchor, 1e6);
+        assert_eq!(camera.scale(), MAX_SCALE);
+        assert_near(camera.unproject(anchor), before);
+
+        // 缩到最小以后再缩小不动，锚点仍在原处
+        let anchor = vec2(50.0, 350.0);
+        let before = camera.unproject(anchor);
+        for _ in 0..20 {
+            camera.zoom_at(anchor, 0.1);
+        }
+        assert!((camera.scale() - MIN_SCALE).abs() < 1e-6);
+        assert_near(camera.unproject(anchor), before);
+    }
+
+    #[test]
+    fn fit_is_clamped() {
+        let mut camera = Camera::new(800, 400);
+        camera.fit(vec2(1.0, 1.0), vec2(1.0, 1.0));
+        assert_eq!(camera.scale(), MAX_SCALE);
+        assert_near(camera.project(vec2(1.0, 1.0)), vec2(400.0, 200.0));
+        camera.fit(vec2(-1e4, -1e4), vec2(1e4, 1e4));
+        assert_eq!(camera.scale(), MIN_SCALE);
+    }
+
+    #[test]
+    fn pan_follows_the_mouse() {
+        let mut camera = Camera::new(800, 400);
+        camera.zoom_at(vec2(0.0, 0.0), 2.5);
+        let grabbed = camera.unproject(vec2(200.0, 200.0));
+        camera.pan(vec2(200.0, 200.0), vec2(500.0, 50.0));
+        assert_near(camera.unproject(vec2(500.0, 50.0)), grabbed);
+    }
+
+    #[test]
+    fn fit_frames_the_rectangle() {
+        let mut camera = Camera::new(800, 400);
+        camera.fit(vec2(1.0, 1.0), vec2(5.0, 2.0));
+        let (min, max) = (camera.project(vec2(1.0, 1.0)), camera.project(vec2(5.0, 2.0)));
+        assert!(camera.contains(min) && camera.contains(max));
+        assert_near((min + max) / 2.0, vec2(400.0, 200.0));
+    }
+
+    #[test]
+    fn rotation_keeps_the_anchor() {
+        let mut camera = Camera::new(800, 400);
+        let anchor = vec2(100.0, 300.0);
+        let before = camera.unproject(anchor);
+        camera.rotate_at(anchor, std::f32::consts::FRAC_PI_2);
+        assert_near(camera.unproject(anchor), before);
+        assert!((camera.rotation() - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+        assert_eq!(camera.scale(), 1.0);
+
+        // 转了 90 度以后，宽矩形要按高度来放
+        camera.fit(vec2(-2.0, -0.5), vec2(2.0, 0.5));
+        assert!((camera.rotation() - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+        assert!((camera.scale() - 1.0 / 2.2).abs() < 1e-5);
+        assert!(camera.contains(camera.project(vec2(2.0, 0.5))));
+    }
+
+    #[test]
+    fn visible_bounds_cover_the_window() {
+        let mut camera = Camera::new(800, 400);
+        let (min, max) = camera.visible_bounds();
+        assert_near(min, vec2(-2.0, -1.0));
+        assert_near(max, vec2(2.0, 1.0));
+        camera.rotate_at(vec2(400.0, 200.0), std::f32::consts::FRAC_PI_2);
+        let (min, max) = camera.visible_bounds();
+        assert_near(min, vec2(-1.0, -2.0));
+        assert_near(max, vec2(1.0, 2.0));
+    }
+
+    #[test]
+    fn projection_follows_the_aspect_ratio() {
+        let mut camera = Camera::new(400, 400);
+        assert_eq!(camera.projection().transform_point3(vec3(1.0, 1.0, 0.0)), vec3(1.0, 1.0, 0.0));
+        camera.resize(1000, 500);
+        assert_eq!(camera.projection().transform_point3(vec3(2.0, 1.0, 0.0)), vec3(1.0, 1.0, 0.0));
+    }
+}
@@ -0,0 +1,238 @@
+//! Subcommands for using the editor from scripts.
+
+use std::{fmt, path::{Path, PathBuf}};
+
+use crate::{document::{self, svg, Document}, headless, script};
+
+pub const USAGE: &str = "\
+Usage:
+    drawing [edit <file>] [--script <script>]      open the editor, optionally with a file
+    drawing render <input> <output.png> [--size WxH]
+    drawing convert <input> <output> [--size WxH]  between .ron and .svg
+    drawing run <script> [--size WxH]              run a script on an empty drawing
+    drawing info <input>
+
+A script given as - is read from standard input.";
+
+const DEFAULT_SIZE: (u32, u32) = (1280, 720);
+/// How closely curves are followed when measuring and offsetting, in view units.
+pub const TOLERANCE: f32 = 1e-4;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Opens the editor with a file, running a script on it first.
+    Edit { file: Option<PathBuf>, script: Option<PathBuf> },
+    Render { input: PathBuf, output: PathBuf, size: (u32, u32) },
+    Convert { input: PathBuf, output: PathBuf, size: (u32, u32) },
+    Info(PathBuf),
+    Run { script: PathBuf, size: (u32, u32) },
+    Help,
+}
+
+impl Command {
+    /// Reads the arguments after the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut size = None;
+        let mut script = None;
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "--size" => {
+                    let value = args.next().ok_or_else(|| Error::Usage("--size needs a value".into()))?;
+                    size = Some(parse_size(&value)?);
+                }
+                "--script" => {
+                    script = Some(args.next().ok_or_else(|| Error::Usage("--script needs a file".into()))?.into());
+                }
+                _ if arg.starts_with("--") => return Err(Error::Usage(format!("unknown option {arg}"))),
+                _ => positional.push(arg),
+            }
+        }
+        let size = size.unwrap_or(DEFAULT_SIZE);
+        let command = match positional.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] | ["edit"] => Command::Edit { file: None, script: script.clone() },
+            ["edit", file] => Command::Edit { file: Some(file.into()), script: script.clone() },
+            ["render", input, output] => Command::Render { input: input.into(), output: output.into(), size },
+            ["convert", input, output] => Command::Convert { input: input.into(), output: output.into(), size },
+            ["info", input] => Command::Info(input.into()),
+            ["run", script] => Command::Run { script: script.into(), size },
+            ["help"] => Command::Help,
+            [command, ..] if ["edit", "render", "convert", "info", "run"].contains(&command) => {
+                return Err(Error::Usage(format!("wrong number of arguments for {command}")));
+            }
+            [command, ..] => return Err(Error::Usage(format!("unknown command {command}"))),
+        };
+        if script.is_some() && !matches!(command, Command::Edit { .. }) {
+            return Err(Error::Usage("--script is only for the editor, use run for scripts alone".into()));
+        }
+        Ok(command)
+    }
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), Error> {
+    let size = s.split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|&(w, h)| w > 0 && h > 0);
+    size.ok_or_else(|| Error::Usage(format!("invalid size {s}, expected e.g. 1280x720")))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Native,
+    Svg,
+    Png,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self, Error> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ron") => Ok(Format::Native),
+            Some("svg") => Ok(Format::Svg),
+            Some("png") => Ok(Format::Png),
+            _ => Err(Error::Format(path.to_owned())),
+        }
+    }
+}
+
+/// Opens a native document or imports an SVG file into a view with the given aspect ratio.
+pub fn load(path: &Path, ratio: f32) -> Result<Document, Error> {
+    match Format::of(path)? {
+        Format::Native => Ok(Document::load(path)?),
+        Format::Svg => Ok(Document::from_shapes(svg::load(path, ratio)?)),
+        Format::Png => Err(Error::Format(path.to_owned())),
+    }
+}
+
+/// Saves in the format the extension of `path` asks for, as seen in a view of `size` pixels.
+pub fn save(document: &Document, path: &Path, (width, height): (u32, u32)) -> Result<(), Error> {
+    match Format::of(path)? {
+        Format::Native => document.save(path)?,
+        Format::Svg => svg::save(document, width, height, path)?,
+        Format::Png => headless::render_png(document.clone(), width, height, path)?,
+    }
+    Ok(())
+}
+
+/// Reads a script from a file, or from standard input for `-`.
+pub fn read_script(path: &Path) -> Result<String, Error> {
+    let source = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    };
+    source.map_err(|e| Error::Script(format!("cannot read {}: {e}", path.display())))
+}
+
+/// Runs everything but `edit`, which needs the window.
+pub fn execute(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Edit { .. } | Command::Help => println!("{USAGE}"),
+        Command::Render { input, output, size: (width, height) } => {
+            if Format::of(&output)? != Format::Png {
+                return Err(Error::Format(output));
+            }
+            let document = load(&input, width as f32 / height as f32)?;
+            headless::render_png(document, width, height, &output)?;
+        }
+        Command::Convert { input, output, size } => {
+            let document = load(&input, size.0 as f32 / size.1 as f32)?;
+            save(&document, &output, size)?;
+        }
+        Command::Run { script: path, size } => {
+            let source = read_script(&path)?;
+            let mut document = Document::new();
+            script::run(&source, &mut document, size).map_err(|e| Error::Script(format!("{}: {e}", path.display())))?;
+        }
+        Command::Info(input) => {
+            let document = load(&input, DEFAULT_SIZE.0 as f32 / DEFAULT_SIZE.1 as f32)?;
+            print!("{}", info(&document));
+        }
+    }
+    Ok(())
+}
+
+fn info(document: &Document) -> String {
+    let shapes: Vec<_> = document.shapes().iter().filter(|shape| !shape.is_empty()).collect();
+    let points: usize = shapes.iter().map(|shape| shape.points().count()).sum();
+    let area: f32 = shapes.iter().map(|shape| shape.area(TOLERANCE)).sum();
+    let perimeter: f32 = shapes.iter().map(|shape| shape.perimeter(TOLERANCE)).sum();
+    let bounds = match document.bounds(TOLERANCE) {
+        Some((min, max)) => format!("({}, {}) .. ({}, {})", min.0, min.1, max.0, max.1),
+        None => "none".to_owned(),
+    };
+    format!("shapes: {}\npoints: {points}\nbounds: {bounds}\narea: {area}\nperimeter: {perimeter}\n", shapes.len())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Usage(String),
+    Format(PathBuf),
+    Document(document::Error),
+    Render(headless::Error),
+    Script(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{e}\n\n{USAGE}"),
+            Error::Format(path) => write!(f, "unsupported file type: {}", path.display()),
+            Error::Document(e) => write!(f, "{e}"),
+            Error::Render(e) => write!(f, "{e}"),
+            Error::Script(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<document::Error> for Error {
+    fn from(e: document::Error) -> Self {
+        Error::Document(e)
+    }
+}
+
+impl From<headless::Error> for Error {
+    fn from(e: headless::Error) -> Self {
+        Error::Render(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Shape, Style};
+
+    fn parse(args: &str) -> Result<Command, Error> {
+        Command::parse(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn subcommands() {
+        assert_eq!(parse("").unwrap(), Command::Edit { file: None, script: None });
+        assert_eq!(parse("edit a.ron --script -").unwrap(), Command::Edit { file: Some("a.ron".into()), script: Some("-".into()) });
+        assert_eq!(parse("render a.svg b.png --size 64x32").unwrap(),
+                   Command::Render { input: "a.svg".into(), output: "b.png".into(), size: (64, 32) });
+        assert_eq!(parse("convert a.ron b.svg").unwrap(),
+                   Command::Convert { input: "a.ron".into(), output: "b.svg".into(), size: DEFAULT_SIZE });
+        assert_eq!(parse("info a.ron").unwrap(), Command::Info("a.ron".into()));
+        assert_eq!(parse("run s.txt --size 64x32").unwrap(), Command::Run { script: "s.txt".into(), size: (64, 32) });
+        assert!(parse("info a.ron --script s.txt").is_err());
+        assert!(parse("info").is_err());
+        assert!(parse("render a b --size 0x3").is_err());
+        assert!(parse("paint a").is_err());
+    }
+
+    #[test]
+    fn info_sums_shapes() {
+        let square = |x: f32| vec![(x, 0.0), (x + 1.0, 0.0), (x + 1.0, 1.0), (x, 1.0)];
+        let document = Document::from_shapes([
+            Shape::from_points(square(0.0), true, true, Style::default()),
+            Shape::from_points(square(2.0), false, false, Style::default()),
+        ]);
+        assert_eq!(info(&document), "shapes: 2\npoints: 8\nbounds: (0, 0) .. (3, 1)\narea: 1\nperimeter: 7\n");
+    }
+}
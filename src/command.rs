@@ -0,0 +1,212 @@
+//! Everything the editor can be told to do from the keyboard or the mouse, by name.
+
+use std::fmt;
+
+/// Number of view bookmarks.
+pub const VIEWS: usize = 9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    NewPath,
+    FillPath,
+    AddHole,
+    ToggleFillRule,
+    Delete,
+    ControlPoint,
+    Arc,
+    NextJoin,
+    NextCap,
+    StrokeColor,
+    FillColor,
+    LessOpacity,
+    MoreOpacity,
+    Thinner,
+    Wider,
+    AddLabel,
+    AlignLabel,
+    NewLayer,
+    DeleteLayer,
+    /// Draws into the layer above the active one.
+    LayerAbove,
+    LayerBelow,
+    RaiseLayer,
+    LowerLayer,
+    ToggleLayerVisibility,
+    ToggleLayerLock,
+    LessLayerOpacity,
+    MoreLayerOpacity,
+    Union,
+    Intersection,
+    /// Cuts the upper selected shapes out of the lowest one.
+    Difference,
+    Xor,
+    /// Grows or shrinks the selected shape by a distance typed into the console.
+    Offset,
+    ToggleGrid,
+    Deselect,
+    ZoomIn,
+    ZoomOut,
+    ZoomToFit,
+    RotateLeft,
+    RotateRight,
+    /// Remembers the view in a bookmark, numbered from 1.
+    SaveView(usize),
+    /// Goes back to the view in a bookmark.
+    View(usize),
+    Save,
+    Open,
+    ExportSvg,
+    ImportSvg,
+    /// Opens the line for typing script commands.
+    Console,
+    Undo,
+    Redo,
+}
+
+impl Command {
+    /// Every command, in the order of the help text.
+    pub fn all() -> Vec<Command> {
+        use Command::*;
+        let mut all = vec![
+            NewPath, FillPath, AddHole, ToggleFillRule, Delete, ControlPoint, Arc, NextJoin, NextCap,
+            Thinner, Wider, StrokeColor, FillColor, LessOpacity, MoreOpacity, AddLabel, AlignLabel, NewLayer,
+            DeleteLayer, LayerAbove, LayerBelow, RaiseLayer, LowerLayer, ToggleLayerVisibility, ToggleLayerLock,
+            LessLayerOpacity, MoreLayerOpacity, Union, Intersection, Difference, Xor, Offset,
+            ToggleGrid, Deselect, ZoomIn, ZoomOut, RotateLeft, RotateRight, ZoomToFit,
+        ];
+        all.extend((1..=VIEWS).map(SaveView));
+        all.extend((1..=VIEWS).map(View));
+        all.extend([Save, Open, ExportSvg, ImportSvg, Console, Undo, Redo]);
+        all
+    }
+    /// The name used in the keymap file.
+    pub fn name(self) -> String {
+        use Command::*;
+        let name = match self {
+            NewPath => "new-path",
+            FillPath => "fill-path",
+            AddHole => "add-hole",
+            ToggleFillRule => "toggle-fill-rule",
+            Delete => "delete",
+            ControlPoint => "control-point",
+            Arc => "arc",
+            NextJoin => "next-join",
+            NextCap => "next-cap",
+            StrokeColor => "stroke-color",
+            FillColor => "fill-color",
+            LessOpacity => "less-opacity",
+            MoreOpacity => "more-opacity",
+            Thinner => "thinner",
+            Wider => "wider",
+            AddLabel => "add-label",
+            AlignLabel => "align-label",
+            NewLayer => "new-layer",
+            DeleteLayer => "delete-layer",
+            LayerAbove => "layer-above",
+            LayerBelow => "layer-below",
+            RaiseLayer => "raise-layer",
+            LowerLayer => "lower-layer",
+            ToggleLayerVisibility => "toggle-layer-visibility",
+            ToggleLayerLock => "toggle-layer-lock",
+            LessLayerOpacity => "less-layer-opacity",
+            MoreLayerOpacity => "more-layer-opacity",
+            Union => "union",
+            Intersection => "intersection",
+            Difference => "difference",
+            Xor => "xor",
+            Offset => "offset",
+            ToggleGrid => "toggle-grid",
+            Deselect => "deselect",
+            ZoomIn => "zoom-in",
+            ZoomOut => "zoom-out",
+            ZoomToFit => "zoom-to-fit",
+            RotateLeft => "rotate-left",
+            RotateRight => "rotate-right",
+            SaveView(n) => return format!("save-view-{n}"),
+            View(n) => return format!("view-{n}"),
+            Save => "save",
+            Open => "open",
+            ExportSvg => "export-svg",
+            ImportSvg => "import-svg",
+            Console => "console",
+            Undo => "undo",
+            Redo => "redo",
+        };
+        name.to_owned()
+    }
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::all().into_iter().find(|command| command.name() == name)
+    }
+    /// What the command does, for the help text; commands that belong together share one.
+    pub fn description(self) -> &'static str {
+        use Command::*;
+        match self {
+            NewPath => "start path",
+            FillPath => "fill and start path",
+            AddHole => "add hole",
+            ToggleFillRule => "toggle fill rule",
+            Delete => "delete the selected label or the path",
+            ControlPoint => "add a curve control point",
+            Arc => "draw an arc",
+            NextJoin => "change joins",
+            NextCap => "change caps",
+            Thinner | Wider => "change width or label size",
+            StrokeColor => "change stroke or label colour",
+            FillColor => "change fill colour",
+            LessOpacity | MoreOpacity => "change opacity",
+            AddLabel => "add a label",
+            AlignLabel => "align the selected label",
+            NewLayer => "add a layer",
+            DeleteLayer => "delete the layer and its paths",
+            LayerAbove | LayerBelow => "draw into another layer",
+            RaiseLayer | LowerLayer => "move the layer up or down",
+            ToggleLayerVisibility => "show or hide the layer",
+            ToggleLayerLock => "lock or unlock the layer",
+            LessLayerOpacity | MoreLayerOpacity => "change layer opacity",
+            Union => "unite the selected shapes",
+            Intersection => "intersect the selected shapes",
+            Difference => "cut the upper selected shapes out of the lowest",
+            Xor => "keep what only one selected shape covers",
+            Offset => "grow or shrink the selected shape",
+            ToggleGrid => "toggle the grid and grid snapping",
+            Deselect => "clear the selection",
+            ZoomIn | ZoomOut => "zoom",
+            RotateLeft | RotateRight => "rotate",
+            ZoomToFit => "fit all shapes",
+            SaveView(_) => "save the view",
+            View(_) => "go back to it",
+            Save => "save",
+            Open => "open",
+            ExportSvg => "export SVG",
+            ImportSvg => "import SVG",
+            Console => "type script commands",
+            Undo => "undo",
+            Redo => "redo",
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique_and_round_trip() {
+        let all = Command::all();
+        for command in &all {
+            assert_eq!(Command::from_name(&command.name()), Some(*command));
+        }
+        let mut names: Vec<String> = all.iter().map(|command| command.name()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), all.len());
+        assert_eq!(Command::from_name("view-3"), Some(Command::View(3)));
+        assert_eq!(Command::from_name("view-0"), None);
+    }
+}
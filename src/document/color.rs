@@ -0,0 +1,112 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Non-premultiplied sRGB colour with components in `0..=1`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const GREEN: Color = Color::rgb(0.0, 1.0, 0.0);
+    pub const TRANSPARENT: Color = Color::BLACK.with_alpha(0.0);
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+    pub const fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+    /// The colour of `self` painted on top of `below`.
+    pub fn over(self, below: Color) -> Self {
+        let a = self.a + below.a * (1.0 - self.a);
+        if a <= 0.0 {
+            return self.with_alpha(0.0);
+        }
+        let mix = |top: f32, bottom: f32| (top * self.a + bottom * below.a * (1.0 - self.a)) / a;
+        Self { r: mix(self.r, below.r), g: mix(self.g, below.g), b: mix(self.b, below.b), a }
+    }
+    /// Reads `#rgb`, `#rrggbb`, `rgb(r, g, b)` and a few colour keywords as used by SVG.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<_>>()?;
+            let (r, g, b) = match digits[..] {
+                [r, g, b] => (r * 17, g * 17, b * 17),
+                [r1, r2, g1, g2, b1, b2] => (r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2),
+                _ => return None,
+            };
+            return Some(Self::rgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0));
+        }
+        if let Some(args) = s.strip_prefix("rgb(").and_then(|s| s.strip_suffix(')')) {
+            let channels: Vec<f32> = args.split(',')
+                .map(|c| {
+                    let c = c.trim();
+                    match c.strip_suffix('%') {
+                        Some(percent) => percent.trim().parse::<f32>().ok().map(|p| p / 100.0),
+                        None => c.parse::<f32>().ok().map(|v| v / 255.0),
+                    }
+                })
+                .collect::<Option<_>>()?;
+            let [r, g, b] = channels[..] else {
+                return None;
+            };
+            return Some(Self::rgb(r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0)));
+        }
+        let (r, g, b) = match s.to_ascii_lowercase().as_str() {
+            "black" => (0, 0, 0),
+            "white" => (255, 255, 255),
+            "red" => (255, 0, 0),
+            "lime" => (0, 255, 0),
+            "green" => (0, 128, 0),
+            "blue" => (0, 0, 255),
+            "yellow" => (255, 255, 0),
+            "cyan" | "aqua" => (0, 255, 255),
+            "magenta" | "fuchsia" => (255, 0, 255),
+            "gray" | "grey" => (128, 128, 128),
+            "orange" => (255, 165, 0),
+            _ => return None,
+        };
+        Some(Self::rgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0))
+    }
+}
+
+/// Formats as `#rrggbb`, leaving out alpha.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        write!(f, "#{:02x}{:02x}{:02x}", byte(self.r), byte(self.g), byte(self.b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        assert_eq!(Color::parse("#0f0"), Some(Color::GREEN.with_alpha(1.0)));
+        assert_eq!(Color::parse("#FF8000").unwrap().to_string(), "#ff8000");
+        assert_eq!(Color::parse("rgb(255, 0, 50%)").unwrap().to_string(), "#ff0080");
+        assert_eq!(Color::parse("White"), Some(Color::WHITE));
+        assert_eq!(Color::parse("#12345"), None);
+        assert_eq!(Color::parse("none"), None);
+    }
+
+    #[test]
+    fn translucent_over_opaque() {
+        let color = Color::WHITE.with_alpha(0.5).over(Color::BLACK);
+        assert_eq!(color.to_array(), [0.5, 0.5, 0.5, 1.0]);
+        let color = Color::WHITE.with_alpha(0.5).over(Color::BLACK.with_alpha(0.5));
+        assert!((color.a - 0.75).abs() < 1e-6 && (color.r - 2.0 / 3.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+
+use super::{Label, Layer, Node, Shape};
+
+pub const DEFAULT_DEPTH: usize = 100;
+
+/// A single reversible change to the shapes, labels or layers of a document.
+///
+/// Applying an edit yields the edit that reverts it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert { index: usize, shape: Shape },
+    Remove { index: usize },
+    Replace { index: usize, shape: Shape },
+    Push { index: usize, node: Node },
+    Pop { index: usize },
+    InsertLabel { index: usize, label: Label },
+    RemoveLabel { index: usize },
+    ReplaceLabel { index: usize, label: Label },
+    /// Replaces the whole list of layers, small enough to copy on every change.
+    Layers { layers: Vec<Layer> },
+}
+
+impl Edit {
+    pub(super) fn apply(self, shapes: &mut Vec<Shape>, labels: &mut Vec<Label>, layers: &mut Vec<Layer>) -> Edit {
+        match self {
+            Edit::Insert { index, shape } => {
+                shapes.insert(index, shape);
+                Edit::Remove { index }
+            }
+            Edit::Remove { index } => Edit::Insert { index, shape: shapes.remove(index) },
+            Edit::Replace { index, shape } => Edit::Replace { index, shape: std::mem::replace(&mut shapes[index], shape) },
+            Edit::Push { index, node } => {
+                shapes[index].push(node);
+                Edit::Pop { index }
+            }
+            Edit::Pop { index } => Edit::Push { index, node: shapes[index].pop().unwrap() },
+            Edit::InsertLabel { index, label } => {
+                labels.insert(index, label);
+                Edit::RemoveLabel { index }
+            }
+            Edit::RemoveLabel { index } => Edit::InsertLabel { index, label: labels.remove(index) },
+            Edit::ReplaceLabel { index, label } => {
+                Edit::ReplaceLabel { index, label: std::mem::replace(&mut labels[index], label) }
+            }
+            Edit::Layers { layers: new } => Edit::Layers { layers: std::mem::replace(layers, new) },
+        }
+    }
+}
+
+/// Undo and redo stacks of edit groups, each group being one user-visible step.
+#[derive(Clone, Debug)]
+pub struct History {
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    group: Vec<Edit>,
+    open_groups: usize,
+    depth: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEPTH)
+    }
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            group: Vec::new(),
+            open_groups: 0,
+            depth,
+        }
+    }
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.undo.len() > depth {
+            self.undo.pop_front();
+        }
+    }
+    /// Starts collecting edits into one step until the matching `end_group`. Groups may nest.
+    pub fn begin_group(&mut self) {
+        self.open_groups += 1;
+    }
+    pub fn end_group(&mut self) {
+        self.open_groups = self.open_groups.saturating_sub(1);
+        if self.open_groups == 0 {
+            self.commit();
+        }
+    }
+
+    pub(super) fn record(&mut self, inverse: Edit) {
+        self.redo.clear();
+        // 连续替换同一个图形时只需保留最早的状态，拖动和输入文字时不会积累大量副本
+        let redundant = match (self.group.last(), &inverse) {
+            (Some(Edit::Replace { index: a, .. }), Edit::Replace { index: b, .. })
+            | (Some(Edit::ReplaceLabel { index: a, .. }), Edit::ReplaceLabel { index: b, .. }) => a == b,
+            (Some(Edit::Layers { .. }), Edit::Layers { .. }) => true,
+            _ => false,
+        };
+        if !redundant {
+            self.group.push(inverse);
+        }
+        if self.open_groups == 0 {
+            self.commit();
+        }
+    }
+    /// Reverts the last step; refused while a group is open, which would otherwise be split in two.
+    pub(super) fn undo(&mut self, shapes: &mut Vec<Shape>, labels: &mut Vec<Label>, layers: &mut Vec<Layer>) -> bool {
+        if self.open_groups > 0 {
+            return false;
+        }
+        let Some(group) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(Self::revert(group, shapes, labels, layers));
+        true
+    }
+    pub(super) fn redo(&mut self, shapes: &mut Vec<Shape>, labels: &mut Vec<Label>, layers: &mut Vec<Layer>) -> bool {
+        if self.open_groups > 0 {
+            return false;
+        }
+        let Some(group) = self.redo.pop() else {
+            return false;
+        };
+        let group = Self::revert(group, shapes, labels, layers);
+        self.push_undo(group);
+        true
+    }
+
+    fn revert(group: Vec<Edit>, shapes: &mut Vec<Shape>, labels: &mut Vec<Label>, layers: &mut Vec<Layer>) -> Vec<Edit> {
+        group.into_iter().rev().map(|edit| edit.apply(shapes, labels, layers)).collect()
+    }
+    fn commit(&mut self) {
+        if self.group.is_empty() {
+            return;
+        }
+        let group = std::mem::take(&mut self.group);
+        self.push_undo(group);
+    }
+    fn push_undo(&mut self, group: Vec<Edit>) {
+        self.undo.push_back(group);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Target {
+        shapes: Vec<Shape>,
+        labels: Vec<Label>,
+        layers: Vec<Layer>,
+    }
+
+    impl Target {
+        fn apply(&mut self, history: &mut History, edit: Edit) {
+            history.record(edit.apply(&mut self.shapes, &mut self.labels, &mut self.layers));
+        }
+        fn undo(&mut self, history: &mut History) -> bool {
+            history.undo(&mut self.shapes, &mut self.labels, &mut self.layers)
+        }
+        fn redo(&mut self, history: &mut History) -> bool {
+            history.redo(&mut self.shapes, &mut self.labels, &mut self.layers)
+        }
+    }
+
+    fn insert(index: usize) -> Edit {
+        Edit::Insert { index, shape: Shape::default() }
+    }
+
+    fn push(index: usize, x: f32) -> Edit {
+        Edit::Push { index, node: Node::line((x, 0.0)) }
+    }
+
+    #[test]
+    fn depth_is_trimmed() {
+        let (mut target, mut history) = (Target::default(), History::new(3));
+        for i in 0..5 {
+            target.apply(&mut history, insert(i));
+        }
+        while target.undo(&mut history) {}
+        // 只能撤回最近的三步
+        assert_eq!(target.shapes.len(), 2);
+
+        history.set_depth(1);
+        while target.redo(&mut history) {}
+        assert_eq!(target.shapes.len(), 5);
+        while target.undo(&mut history) {}
+        assert_eq!(target.shapes.len(), 4);
+    }
+
+    #[test]
+    fn new_edits_drop_the_redo_steps() {
+        let (mut target, mut history) = (Target::default(), History::default());
+        target.apply(&mut history, insert(0));
+        target.apply(&mut history, push(0, 1.0));
+        assert!(target.undo(&mut history));
+        target.apply(&mut history, push(0, 2.0));
+        assert!(!target.redo(&mut history));
+        assert_eq!(target.shapes[0].points().copied().collect::<Vec<_>>(), [(2.0, 0.0)]);
+    }
+
+    #[test]
+    fn nested_groups_are_one_step() {
+        let (mut target, mut history) = (Target::default(), History::default());
+        target.apply(&mut history, insert(0));
+        history.begin_group();
+        target.apply(&mut history, push(0, 1.0));
+        history.begin_group();
+        target.apply(&mut history, push(0, 2.0));
+        history.end_group();
+        // 组还没关上，撤销会把它拆开
+        assert!(!target.undo(&mut history));
+        target.apply(&mut history, push(0, 3.0));
+        history.end_group();
+
+        assert!(target.undo(&mut history));
+        assert_eq!(target.shapes, [Shape::default()]);
+        assert!(target.redo(&mut history));
+        assert_eq!(target.shapes[0].points().count(), 3);
+    }
+
+    #[test]
+    fn repeated_replacements_keep_the_first_state() {
+        let (mut target, mut history) = (Target::default(), History::default());
+        target.apply(&mut history, insert(0));
+        target.apply(&mut history, Edit::InsertLabel { index: 0, label: Label::new((0.0, 0.0), "") });
+        history.begin_group();
+        for text in ["a", "ab", "abc"] {
+            let mut shape = target.shapes[0].clone();
+            shape.push(Node::line((text.len() as f32, 0.0)));
+            target.apply(&mut history, Edit::Replace { index: 0, shape });
+            target.apply(&mut history, Edit::ReplaceLabel { index: 0, label: Label::new((0.0, 0.0), text) });
+        }
+        history.end_group();
+        // 交替替换图形和文字时不能合并
+        assert_eq!(history.undo.back().unwrap().len(), 6);
+
+        history.begin_group();
+        for text in ["x", "xy"] {
+            target.apply(&mut history, Edit::ReplaceLabel { index: 0, label: Label::new((0.0, 0.0), text) });
+        }
+        history.end_group();
+        assert_eq!(history.undo.back().unwrap().len(), 1);
+        assert!(target.undo(&mut history));
+        assert_eq!(target.labels[0].text, "abc");
+        assert!(target.undo(&mut history));
+        assert_eq!(target.labels[0].text, "");
+        assert_eq!(target.shapes, [Shape::default()]);
+    }
+}
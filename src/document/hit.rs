@@ -0,0 +1,100 @@
+//! Finding what lies under the cursor.
+
+use glam::Vec2;
+
+use super::Shape;
+use crate::geometry::fill::winding;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Vertex { contour: usize, node: usize },
+    /// The segment leading to `node`.
+    Edge { contour: usize, node: usize },
+    Fill,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hit {
+    pub shape: usize,
+    pub target: Target,
+}
+
+/// Finds the topmost shape under `point` among the shapes at `order`, listed from the bottom up.
+///
+/// Vertices within `radius` win over edges, which are hit within `radius` or half their stroke width,
+/// which in turn win over the filled interior. Curves are flattened with `tolerance`.
+pub fn hit_test(shapes: &[Shape], order: &[usize], point: (f32, f32), radius: f32, tolerance: f32) -> Option<Hit> {
+    let point = Vec2::from(point);
+    let vertex = order.iter().rev().find_map(|&shape| {
+        shapes[shape].contours.iter().enumerate().find_map(|(contour, c)| {
+            c.iter().position(|n| Vec2::from(n.point).distance(point) <= radius)
+                .map(|node| Hit { shape, target: Target::Vertex { contour, node } })
+        })
+    });
+    if vertex.is_some() {
+        return vertex;
+    }
+    for &index in order.iter().rev() {
+        let shape = &shapes[index];
+        if let Some(target) = hit_edge(shape, point, radius.max(shape.style.width / 2.0), tolerance) {
+            return Some(Hit { shape: index, target });
+        }
+        if shape.fill && !shape.is_empty() {
+            let contours = shape.flatten(tolerance);
+            if shape.fill_rule.is_inside(winding(contours.iter().map(Vec::as_slice), point)) {
+                return Some(Hit { shape: index, target: Target::Fill });
+            }
+        }
+    }
+    None
+}
+
+fn hit_edge(shape: &Shape, point: Vec2, radius: f32, tolerance: f32) -> Option<Target> {
+    for (contour, nodes) in shape.contours.iter().enumerate() {
+        let first = if shape.is_contour_closed(contour) { 0 } else { 1 };
+        for node in first..nodes.len() {
+            let from = Vec2::from(nodes[(node + nodes.len() - 1) % nodes.len()].point);
+            let mut points = vec![from];
+            nodes[node].segment.flatten(from, nodes[node].point.into(), tolerance, &mut points);
+            if points.windows(2).any(|s| distance_to_segment(point, s[0], s[1]) <= radius) {
+                return Some(Target::Edge { contour, node });
+            }
+        }
+    }
+    None
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab == Vec2::ZERO { 0.0 } else { ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0) };
+    p.distance(a + ab * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Style;
+
+    fn shapes() -> Vec<Shape> {
+        let square = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let line = vec![(0.5, -1.0), (0.5, 2.0)];
+        vec![
+            Shape::from_points(square, true, true, Style::default()),
+            Shape::from_points(line, false, false, Style::default()),
+        ]
+    }
+
+    #[test]
+    fn vertices_before_edges_before_fill() {
+        let shapes = shapes();
+        let hit = |x, y| hit_test(&shapes, &[0, 1], (x, y), 0.05, 0.01);
+        assert_eq!(hit(1.02, 0.98), Some(Hit { shape: 0, target: Target::Vertex { contour: 0, node: 2 } }));
+        assert_eq!(hit(0.0, 0.5), Some(Hit { shape: 0, target: Target::Edge { contour: 0, node: 0 } }));
+        assert_eq!(hit(0.5, 0.5), Some(Hit { shape: 1, target: Target::Edge { contour: 0, node: 1 } }));
+        assert_eq!(hit(0.25, 0.5), Some(Hit { shape: 0, target: Target::Fill }));
+        assert_eq!(hit(2.0, 0.5), None);
+        // 只看列出的图形，后列出的在上面
+        assert_eq!(hit_test(&shapes, &[1, 0], (0.5, 0.5), 0.05, 0.01).unwrap().shape, 0);
+        assert_eq!(hit_test(&shapes, &[0], (0.5, 1.5), 0.05, 0.01), None);
+    }
+}
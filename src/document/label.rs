@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+/// Which end of a label its position is at.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn next(self) -> Self {
+        match self {
+            Align::Left => Align::Center,
+            Align::Center => Align::Right,
+            Align::Right => Align::Left,
+        }
+    }
+}
+
+/// A line of text placed in the drawing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub text: String,
+    /// A point on the baseline, at the start, middle or end of the text depending on `align`.
+    pub position: (f32, f32),
+    /// Font size in world units, as for the `font-size` of SVG.
+    #[serde(default = "default_size")]
+    pub size: f32,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub align: Align,
+}
+
+fn default_size() -> f32 {
+    0.08
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+impl Label {
+    pub fn new(position: (f32, f32), text: impl Into<String>) -> Self {
+        Self { text: text.into(), position, size: default_size(), color: default_color(), align: Align::default() }
+    }
+}
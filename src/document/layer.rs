@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// A named group of shapes drawn together; shapes refer to their layer by `id`, which never changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub id: u32,
+    pub name: String,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    /// Locked layers are drawn but their shapes cannot be selected or changed.
+    #[serde(default)]
+    pub locked: bool,
+    /// Fades the layer as a whole, like the opacity of an SVG group: where its shapes overlap, the
+    /// lower ones do not show through.
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+impl Layer {
+    pub fn new(id: u32, name: impl Into<String>) -> Self {
+        Self { id, name: name.into(), visible: true, locked: false, opacity: 1.0 }
+    }
+    pub fn is_editable(&self) -> bool {
+        self.visible && !self.locked
+    }
+}
+
+pub(super) fn default_layers() -> Vec<Layer> {
+    vec![Layer::new(0, "Layer 1")]
+}
@@ -0,0 +1,67 @@
+//! Readers for older revisions of the document format.
+
+use serde::Deserialize;
+
+use super::{FillRule, Node, Shape, Style};
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+pub fn version(s: &str) -> Result<u32, ron::error::SpannedError> {
+    Ok(ron::from_str::<Header>(s)?.version)
+}
+
+#[derive(Deserialize)]
+struct ShapeV1 {
+    points: Vec<(f32, f32)>,
+    fill: bool,
+    closed: bool,
+    #[serde(default)]
+    style: Style,
+}
+
+#[derive(Deserialize)]
+struct DocumentV1 {
+    shapes: Vec<ShapeV1>,
+}
+
+/// Version 1 stored a single list of points per shape.
+pub fn v1(s: &str) -> Result<Vec<Shape>, ron::error::SpannedError> {
+    let document: DocumentV1 = ron::from_str(s)?;
+    Ok(document.shapes.into_iter()
+        .map(|shape| Shape::from_points(shape.points, shape.fill, shape.closed, shape.style))
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct ShapeV2 {
+    contours: Vec<Vec<(f32, f32)>>,
+    fill: bool,
+    #[serde(default)]
+    fill_rule: FillRule,
+    closed: bool,
+    #[serde(default)]
+    style: Style,
+}
+
+#[derive(Deserialize)]
+struct DocumentV2 {
+    shapes: Vec<ShapeV2>,
+}
+
+/// Version 2 stored contours as plain points, before curve segments existed.
+pub fn v2(s: &str) -> Result<Vec<Shape>, ron::error::SpannedError> {
+    let document: DocumentV2 = ron::from_str(s)?;
+    Ok(document.shapes.into_iter()
+        .map(|shape| Shape {
+            contours: shape.contours.into_iter().map(|c| c.into_iter().map(Node::line).collect()).collect(),
+            fill: shape.fill,
+            fill_rule: shape.fill_rule,
+            closed: shape.closed,
+            style: shape.style,
+            layer: 0,
+        })
+        .collect())
+}
@@ -0,0 +1,1008 @@
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use glam::{Affine2, Vec2};
+use serde::{Deserialize, Serialize};
+
+mod color;
+pub mod history;
+pub mod hit;
+mod label;
+mod layer;
+mod legacy;
+pub mod snap;
+pub mod svg;
+pub mod transform;
+
+pub use color::Color;
+pub use label::{Align, Label};
+pub use layer::Layer;
+use history::{Edit, History};
+
+pub use crate::geometry::boolean::Operation;
+use crate::geometry::boolean::{boolean, split_outlines};
+pub use crate::geometry::curve::Segment;
+pub use crate::geometry::fill::FillRule;
+use crate::geometry::fill::tessellate;
+pub use crate::geometry::offset::Corner;
+use crate::geometry::offset::{offset, outline};
+pub use crate::geometry::stroke::{Cap, Join, Stroke};
+
+pub const VERSION: u32 = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub width: f32,
+    pub join: Join,
+    pub cap: Cap,
+    pub miter_limit: f32,
+    pub fill_color: Color,
+    pub stroke_color: Color,
+    /// Fades the fill and the stroke together, as SVG's `opacity` does: the stroke is composited onto
+    /// the fill first, so the fill shows through it no more than at full opacity.
+    pub opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            width: 0.02,
+            join: Join::default(),
+            cap: Cap::default(),
+            miter_limit: 4.0,
+            fill_color: Color::GREEN,
+            stroke_color: Color::GREEN,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Style {
+    /// The colour where the fill, the stroke or both show, with the stroke on top.
+    pub fn paint(&self, fill: bool, stroke: bool) -> Color {
+        let fill = if fill { self.fill_color } else { Color::TRANSPARENT };
+        let stroke = if stroke { self.stroke_color } else { Color::TRANSPARENT };
+        let color = stroke.over(fill);
+        color.with_alpha(color.a * self.opacity)
+    }
+    pub fn stroke(&self) -> Stroke {
+        Stroke {
+            width: self.width,
+            join: self.join,
+            cap: self.cap,
+            miter_limit: self.miter_limit,
+        }
+    }
+}
+
+/// A point of a contour together with the segment that leads to it from the previous point.
+///
+/// The segment of the first point closes the contour, it is only used when the contour is closed.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub point: (f32, f32),
+    #[serde(default)]
+    pub segment: Segment,
+}
+
+impl Node {
+    pub fn line(point: (f32, f32)) -> Self {
+        Self { point, segment: Segment::Line }
+    }
+}
+
+pub type Contour = Vec<Node>;
+
+/// A path made of one or more contours; every contour after the first one cuts a hole or adds an
+/// island depending on `fill_rule`.
+///
+/// All contours except the last one are closed, the last one is closed once `closed` is set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Shape {
+    pub contours: Vec<Contour>,
+    pub fill: bool,
+    #[serde(default)]
+    pub fill_rule: FillRule,
+    pub closed: bool,
+    #[serde(default)]
+    pub style: Style,
+    /// Id of the layer the shape is drawn in.
+    #[serde(default)]
+    pub layer: u32,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self {
+            contours: vec![Contour::new()],
+            fill: false,
+            fill_rule: FillRule::default(),
+            closed: false,
+            style: Style::default(),
+            layer: 0,
+        }
+    }
+}
+
+impl Shape {
+    pub fn from_points(points: Vec<(f32, f32)>, fill: bool, closed: bool, style: Style) -> Self {
+        let contour = points.into_iter().map(Node::line).collect();
+        Self { contours: vec![contour], fill, closed, style, ..Self::default() }
+    }
+    pub fn push(&mut self, node: Node) {
+        match self.contours.last_mut() {
+            Some(contour) => contour.push(node),
+            None => self.contours.push(vec![node]),
+        }
+    }
+    pub fn pop(&mut self) -> Option<Node> {
+        self.contours.last_mut()?.pop()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.contours.iter().all(Vec::is_empty)
+    }
+    pub fn points(&self) -> impl Iterator<Item = &(f32, f32)> {
+        self.contours.iter().flatten().map(|node| &node.point)
+    }
+    pub fn is_contour_closed(&self, contour: usize) -> bool {
+        self.closed || contour + 1 < self.contours.len()
+    }
+    pub fn has_curves(&self) -> bool {
+        self.contours.iter().enumerate().any(|(i, contour)| {
+            let skip = if self.is_contour_closed(i) { 0 } else { 1 };
+            contour.iter().skip(skip).any(|node| node.segment != Segment::Line)
+        })
+    }
+    /// Turns every contour into a polyline whose distance to the curves stays within `tolerance`.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec<(f32, f32)>> {
+        self.contours.iter().enumerate()
+            .map(|(i, contour)| flatten(contour, self.is_contour_closed(i), tolerance))
+            .collect()
+    }
+    /// Area covered by the fill under its fill rule, 0 for shapes that are not filled.
+    pub fn area(&self, tolerance: f32) -> f32 {
+        if !self.fill {
+            return 0.0;
+        }
+        let contours = self.flatten(tolerance);
+        tessellate(contours.iter().map(Vec::as_slice), self.fill_rule).area()
+    }
+    /// Length of all outlines, counting the closing segments of closed contours.
+    pub fn perimeter(&self, tolerance: f32) -> f32 {
+        self.flatten(tolerance).iter().enumerate()
+            .map(|(i, points)| {
+                let length: f32 = points.windows(2).map(|s| Vec2::from(s[0]).distance(s[1].into())).sum();
+                match (self.is_contour_closed(i), points.first(), points.last()) {
+                    (true, Some(&first), Some(&last)) => length + Vec2::from(last).distance(first.into()),
+                    _ => length,
+                }
+            })
+            .sum()
+    }
+    /// Lower left and upper right corners around the outlines, without the stroke width.
+    pub fn bounds(&self, tolerance: f32) -> Option<((f32, f32), (f32, f32))> {
+        let points: Vec<Vec2> = self.flatten(tolerance).into_iter().flatten().map(Vec2::from).collect();
+        let first = *points.first()?;
+        let (min, max) = points.iter().fold((first, first), |(min, max), &p| (min.min(p), max.max(p)));
+        Some((min.into(), max.into()))
+    }
+    /// Applies `transform` to the points, control points and stroke width.
+    ///
+    /// Arcs keep their shape under translation, rotation, uniform scaling and mirroring; any other
+    /// transform first turns them into cubic curves.
+    pub fn transform(&mut self, transform: Affine2) {
+        let m = transform.matrix2;
+        let similar = (m.x_axis.dot(m.y_axis).abs() <= 1e-6 * m.x_axis.length_squared())
+            && (m.x_axis.length() - m.y_axis.length()).abs() <= 1e-6 * m.x_axis.length();
+        if !similar {
+            for i in 0..self.contours.len() {
+                let closed = self.is_contour_closed(i);
+                arcs_to_cubics(&mut self.contours[i], closed);
+            }
+        }
+        let det = m.determinant();
+        let scale = det.abs().sqrt();
+        let angle = m.x_axis.y.atan2(m.x_axis.x).to_degrees();
+        let apply = |p: &mut (f32, f32)| *p = transform.transform_point2((*p).into()).into();
+        for node in self.contours.iter_mut().flatten() {
+            apply(&mut node.point);
+            match &mut node.segment {
+                Segment::Line => {}
+                Segment::Quadratic(c) => apply(c),
+                Segment::Cubic(c1, c2) => {
+                    apply(c1);
+                    apply(c2);
+                }
+                Segment::Arc { radii, rotation, sweep, .. } => {
+                    *radii = (radii.0 * scale, radii.1 * scale);
+                    // 镜像会让椭圆的转角反向，同时改变绕行方向
+                    if det < 0.0 {
+                        *rotation = angle - *rotation;
+                        *sweep = !*sweep;
+                    } else {
+                        *rotation += angle;
+                    }
+                }
+            }
+        }
+        self.style.width *= scale;
+    }
+}
+
+fn flatten(contour: &[Node], closed: bool, tolerance: f32) -> Vec<(f32, f32)> {
+    let (Some(first), Some(last)) = (contour.first(), contour.last()) else {
+        return Vec::new();
+    };
+    let mut points = vec![Vec2::from(first.point)];
+    for pair in contour.windows(2) {
+        pair[1].segment.flatten(pair[0].point.into(), pair[1].point.into(), tolerance, &mut points);
+    }
+    if closed && contour.len() > 1 && first.segment != Segment::Line {
+        first.segment.flatten(last.point.into(), first.point.into(), tolerance, &mut points);
+        // 最后一个点与起点重合
+        points.pop();
+    }
+    points.into_iter().map(Into::into).collect()
+}
+
+fn arcs_to_cubics(contour: &mut Contour, closed: bool) {
+    let n = contour.len();
+    let mut result = Vec::with_capacity(n);
+    // 闭合段的中间节点放在轮廓末尾，最后一段留给第一个节点
+    let mut closing = Vec::new();
+    for i in 0..n {
+        let node = contour[i];
+        let Segment::Arc { radii, rotation, large_arc, sweep } = node.segment else {
+            result.push(node);
+            continue;
+        };
+        if i == 0 && !closed {
+            result.push(Node::line(node.point));
+            continue;
+        }
+        let from = Vec2::from(contour[(i + n - 1) % n].point);
+        let arc = crate::geometry::curve::Arc::from_endpoints(from, radii.into(), rotation, large_arc, sweep, node.point.into());
+        let Some(arc) = arc else {
+            result.push(Node::line(node.point));
+            continue;
+        };
+        let mut nodes: Vec<Node> = arc.to_cubics().into_iter()
+            .map(|(c1, c2, end)| Node { point: end.into(), segment: Segment::Cubic(c1.into(), c2.into()) })
+            .collect();
+        // 保留原来的端点，避免浮点误差挪动它
+        nodes.last_mut().unwrap().point = node.point;
+        if i == 0 {
+            result.push(nodes.pop().unwrap());
+            closing = nodes;
+        } else {
+            result.extend(nodes);
+        }
+    }
+    result.extend(closing);
+    *contour = result;
+}
+
+/// Everything that gets drawn, kept apart from the GPU state so it can be saved and reloaded.
+///
+/// The last shape is always the path currently being drawn, it belongs to the active layer.
+/// Every change goes through [`Edit`]s so that it can be undone.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Document {
+    version: u32,
+    shapes: Vec<Shape>,
+    /// Drawn above all shapes, in order.
+    #[serde(default)]
+    labels: Vec<Label>,
+    /// From the bottom to the top; there is always at least one.
+    #[serde(default = "layer::default_layers")]
+    layers: Vec<Layer>,
+    /// Id of the layer new paths go into.
+    #[serde(default)]
+    active_layer: u32,
+    #[serde(skip)]
+    history: History,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self {
+            version: VERSION,
+            shapes: vec![Shape::default()],
+            labels: Vec::new(),
+            layers: layer::default_layers(),
+            active_layer: 0,
+            history: History::default(),
+        }
+    }
+    /// Starts a document from finished `shapes`, with nothing to undo.
+    pub fn from_shapes(shapes: impl IntoIterator<Item = Shape>) -> Self {
+        let mut document = Self::new();
+        document.shapes.splice(0..0, shapes);
+        document
+    }
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+    pub fn current(&self) -> &Shape {
+        self.shapes.last().unwrap()
+    }
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+    /// Corners around all shapes, see `Shape::bounds`, and the positions of the labels.
+    pub fn bounds(&self, tolerance: f32) -> Option<((f32, f32), (f32, f32))> {
+        self.shapes.iter().filter_map(|shape| shape.bounds(tolerance))
+            .chain(self.labels.iter().map(|label| (label.position, label.position)))
+            .reduce(|(min, max), (lo, hi)| ((min.0.min(lo.0), min.1.min(lo.1)), (max.0.max(hi.0), max.1.max(hi.1))))
+    }
+    /// Like [`Document::bounds`], widened by half the widest stroke so that the strokes fit too.
+    pub fn painted_bounds(&self, tolerance: f32) -> Option<((f32, f32), (f32, f32))> {
+        let (min, max) = self.bounds(tolerance)?;
+        // 描边有一半落在轮廓外面
+        let margin = self.shapes.iter()
+            .filter(|shape| !shape.is_empty())
+            .map(|shape| shape.style.width / 2.0)
+            .fold(0.0, f32::max);
+        Some(((min.0 - margin, min.1 - margin), (max.0 + margin, max.1 + margin)))
+    }
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+    pub fn apply(&mut self, edit: Edit) {
+        let inverse = edit.apply(&mut self.shapes, &mut self.labels, &mut self.layers);
+        self.history.record(inverse);
+        self.check_active_layer();
+    }
+    /// Applies several edits as a single undo step.
+    pub fn apply_all(&mut self, edits: impl IntoIterator<Item = Edit>) {
+        self.history.begin_group();
+        for edit in edits {
+            self.apply(edit);
+        }
+        self.history.end_group();
+    }
+    pub fn undo(&mut self) -> bool {
+        let undone = self.history.undo(&mut self.shapes, &mut self.labels, &mut self.layers);
+        self.check_active_layer();
+        undone
+    }
+    pub fn redo(&mut self) -> bool {
+        let redone = self.history.redo(&mut self.shapes, &mut self.labels, &mut self.layers);
+        self.check_active_layer();
+        redone
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+    /// Index of the layer new paths go into.
+    pub fn active_layer(&self) -> usize {
+        self.layer_index(self.active_layer).unwrap_or(0)
+    }
+    /// Makes new paths go into the layer at `index`, together with the path being drawn.
+    pub fn set_active_layer(&mut self, index: usize) {
+        if let Some(layer) = self.layers.get(index) {
+            self.active_layer = layer.id;
+        }
+    }
+    fn layer_index(&self, id: u32) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.id == id)
+    }
+    /// Index of the layer of the shape at `index`.
+    pub fn layer_of(&self, index: usize) -> usize {
+        if index == self.current_index() {
+            return self.active_layer();
+        }
+        self.layer_index(self.shapes[index].layer).unwrap_or(0)
+    }
+    /// Whether the shape at `index` may be selected and changed.
+    pub fn is_editable(&self, index: usize) -> bool {
+        self.layers[self.layer_of(index)].is_editable()
+    }
+    /// Indices of the shapes of visible layers, in the order they are drawn.
+    pub fn draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<(usize, usize)> = (0..self.shapes.len())
+            .map(|index| (self.layer_of(index), index))
+            .filter(|&(layer, _)| self.layers[layer].visible)
+            .collect();
+        order.sort_unstable();
+        order.into_iter().map(|(_, index)| index).collect()
+    }
+    /// Adds an empty layer right above the active one and makes it active, returns its index.
+    pub fn add_layer(&mut self, name: impl Into<String>) -> usize {
+        let id = self.layers.iter().map(|layer| layer.id).max().map_or(0, |id| id + 1);
+        let index = self.active_layer() + 1;
+        let mut layers = self.layers.clone();
+        layers.insert(index, Layer::new(id, name));
+        self.apply(Edit::Layers { layers });
+        self.active_layer = id;
+        index
+    }
+    /// Changes the name, visibility, lock or opacity of the layer at `index`; its id is kept.
+    pub fn set_layer(&mut self, index: usize, layer: Layer) {
+        let mut layers = self.layers.clone();
+        layers[index] = Layer { id: layers[index].id, ..layer };
+        self.apply(Edit::Layers { layers });
+    }
+    /// Moves the layer at `index` to `to`, above or below the layers in between.
+    pub fn move_layer(&mut self, index: usize, to: usize) {
+        if index == to || to >= self.layers.len() {
+            return;
+        }
+        let mut layers = self.layers.clone();
+        let layer = layers.remove(index);
+        layers.insert(to, layer);
+        self.apply(Edit::Layers { layers });
+    }
+    /// Removes the layer at `index` with all its shapes, returns false for the only layer.
+    pub fn remove_layer(&mut self, index: usize) -> bool {
+        if self.layers.len() < 2 {
+            return false;
+        }
+        let id = self.layers[index].id;
+        let current = self.current_index();
+        let mut layers = self.layers.clone();
+        layers.remove(index);
+        self.history.begin_group();
+        for i in (0..current).rev() {
+            if self.shapes[i].layer == id {
+                self.apply(Edit::Remove { index: i });
+            }
+        }
+        self.apply(Edit::Layers { layers });
+        self.history.end_group();
+        true
+    }
+    /// Falls back to the top layer when the active one went away, e.g. after undoing its creation.
+    fn check_active_layer(&mut self) {
+        if self.layer_index(self.active_layer).is_none() {
+            self.active_layer = self.layers.last().unwrap().id;
+        }
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.shapes.len() - 1
+    }
+    pub fn push_node(&mut self, node: Node) {
+        if !self.is_editable(self.current_index()) {
+            return;
+        }
+        self.apply(Edit::Push { index: self.current_index(), node });
+    }
+    /// Finishes the current path and starts a new one, returns false if there was nothing to finish.
+    pub fn new_path(&mut self, fill: bool) -> bool {
+        self.finish_path(fill, true)
+    }
+    /// Like `new_path`, but the finished path may be left open; open paths are never filled.
+    pub fn finish_path(&mut self, fill: bool, closed: bool) -> bool {
+        let index = self.current_index();
+        if self.current().is_empty() || !self.is_editable(index) {
+            return false;
+        }
+        let layer = self.layers[self.active_layer()].id;
+        let finished = Shape { fill: fill && closed, closed, layer, ..self.current().clone() };
+        // 新路径沿用上一条路径的样式
+        let next = Shape { style: finished.style, ..Shape::default() };
+        self.apply_all([
+            Edit::Replace { index, shape: finished },
+            Edit::Insert { index: index + 1, shape: next },
+        ]);
+        true
+    }
+    /// Closes the contour being drawn and starts another one in the same shape, used to cut holes.
+    pub fn new_contour(&mut self) -> bool {
+        let index = self.current_index();
+        if self.current().contours.last().is_none_or(|contour| contour.len() < 3) || !self.is_editable(index) {
+            return false;
+        }
+        let mut shape = self.current().clone();
+        shape.contours.push(Contour::new());
+        self.apply(Edit::Replace { index, shape });
+        true
+    }
+    /// Moves a point by `offset`, together with the cubic handles attached to it.
+    pub fn move_vertex(&mut self, index: usize, contour: usize, node: usize, offset: (f32, f32)) {
+        if !self.is_editable(index) {
+            return;
+        }
+        let mut shape = self.shapes[index].clone();
+        let nodes = &mut shape.contours[contour];
+        let shift = |p: &mut (f32, f32)| *p = (p.0 + offset.0, p.1 + offset.1);
+        shift(&mut nodes[node].point);
+        if let Segment::Cubic(_, c2) = &mut nodes[node].segment {
+            shift(c2);
+        }
+        let next = (node + 1) % nodes.len();
+        if next != node {
+            if let Segment::Cubic(c1, _) = &mut nodes[next].segment {
+                shift(c1);
+            }
+        }
+        self.apply(Edit::Replace { index, shape });
+    }
+    pub fn translate_shape(&mut self, index: usize, offset: (f32, f32)) {
+        self.transform_shape(index, Affine2::from_translation(offset.into()));
+    }
+    /// Moves, scales or rotates the shape at `index`, see `Shape::transform`.
+    pub fn transform_shape(&mut self, index: usize, transform: Affine2) {
+        if !self.is_editable(index) {
+            return;
+        }
+        let mut shape = self.shapes[index].clone();
+        shape.transform(transform);
+        self.apply(Edit::Replace { index, shape });
+    }
+    /// Restyles the shape at `index`; the path being drawn passes its style on to later paths.
+    pub fn set_style(&mut self, index: usize, style: Style) {
+        if !self.is_editable(index) {
+            return;
+        }
+        self.apply(Edit::Replace { index, shape: Shape { style, ..self.shapes[index].clone() } });
+    }
+    pub fn toggle_fill_rule(&mut self) {
+        let index = self.current_index();
+        if !self.is_editable(index) {
+            return;
+        }
+        let fill_rule = self.current().fill_rule.toggled();
+        self.apply(Edit::Replace { index, shape: Shape { fill_rule, ..self.current().clone() } });
+    }
+    /// Replaces the finished shapes at `indices` with the filled shapes `operation` makes of them,
+    /// folded from the lowest one up; the results take the place, style and layer of the lowest.
+    /// Returns where the results went, which is empty when nothing is left, or `None` unless at least
+    /// two editable shapes are given.
+    pub fn combine(&mut self, indices: &[usize], operation: Operation, tolerance: f32) -> Option<Range<usize>> {
+        let order: Vec<usize> = self.draw_order().into_iter()
+            .filter(|&index| indices.contains(&index) && index != self.current_index() && self.is_editable(index))
+            .collect();
+        let (&bottom, rest) = order.split_first()?;
+        if rest.is_empty() {
+            return None;
+        }
+        let first = &self.shapes[bottom];
+        let mut result = first.flatten(tolerance);
+        let mut rule = first.fill_rule;
+        for &index in rest {
+            let shape = &self.shapes[index];
+            result = boolean(&result, rule, &shape.flatten(tolerance), shape.fill_rule, operation);
+            rule = FillRule::NonZero;
+        }
+        Some(self.replace_with_area(order, bottom, result))
+    }
+    /// Grows the finished shape at `index` by `distance`, or shrinks it for negative distances, and
+    /// replaces it with the filled shapes that make up the result; open contours are outlined,
+    /// reaching the distance to either side. `corner` shapes the corners and the ends of open
+    /// contours, miters longer than the miter limit of the shape are squared off.
+    /// Returns where the results went like `combine`, or `None` if the shape cannot be changed.
+    pub fn offset_shape(&mut self, index: usize, distance: f32, corner: Corner, tolerance: f32) -> Option<Range<usize>> {
+        let shape = &self.shapes[index];
+        if index == self.current_index() || shape.is_empty() || !self.is_editable(index) {
+            return None;
+        }
+        let limit = shape.style.miter_limit;
+        let (mut areas, mut lines) = (Vec::new(), Vec::new());
+        for (i, contour) in shape.flatten(tolerance).into_iter().enumerate() {
+            if shape.is_contour_closed(i) { areas.push(contour) } else { lines.push(contour) }
+        }
+        let mut result = offset(&areas, shape.fill_rule, distance, corner, limit, tolerance);
+        for line in lines {
+            let outlined = outline(&line, distance, corner, limit, tolerance);
+            result = boolean(&result, FillRule::NonZero, &outlined, FillRule::NonZero, Operation::Union);
+        }
+        Some(self.replace_with_area(vec![index], index, result))
+    }
+    /// Removes the shapes at `removed` and puts filled shapes covering `contours` where the one at
+    /// `at` was, with its style and layer, as one undo step; returns where they went.
+    fn replace_with_area(&mut self, mut removed: Vec<usize>, at: usize, contours: Vec<Vec<(f32, f32)>>) -> Range<usize> {
+        let template = Shape { contours: Vec::new(), fill: true, fill_rule: FillRule::NonZero, closed: true, ..self.shapes[at].clone() };
+        let shapes: Vec<Shape> = split_outlines(contours).into_iter()
+            .map(|contours| Shape {
+                contours: contours.into_iter().map(|contour| contour.into_iter().map(Node::line).collect()).collect(),
+                ..template.clone()
+            })
+            .collect();
+
+        removed.sort_unstable();
+        let at = at - removed.iter().filter(|&&index| index < at).count();
+        self.history.begin_group();
+        for &index in removed.iter().rev() {
+            self.apply(Edit::Remove { index });
+        }
+        let count = shapes.len();
+        for (i, shape) in shapes.into_iter().enumerate() {
+            self.apply(Edit::Insert { index: at + i, shape });
+        }
+        self.history.end_group();
+        at..at + count
+    }
+    /// Adds finished shapes to the active layer, below the path currently being drawn.
+    pub fn insert_shapes(&mut self, shapes: impl IntoIterator<Item = Shape>) {
+        let current = self.current_index();
+        let layer = self.layers[self.active_layer()].id;
+        self.apply_all(shapes.into_iter().enumerate()
+            .map(|(i, shape)| Edit::Insert { index: current + i, shape: Shape { layer, ..shape } }));
+    }
+    /// The last finished path of the active layer.
+    pub fn last_path(&self) -> Option<usize> {
+        let active = self.active_layer();
+        (0..self.current_index()).rev().find(|&index| self.layer_of(index) == active)
+    }
+    /// Clears the path being drawn, or removes the last finished path of the active layer when
+    /// nothing is being drawn.
+    pub fn delete_path(&mut self) {
+        let index = self.current_index();
+        if !self.is_editable(index) {
+            return;
+        }
+        if !self.current().is_empty() {
+            self.apply(Edit::Replace { index, shape: Shape { style: self.current().style, ..Shape::default() } });
+        } else if let Some(index) = self.last_path() {
+            self.apply(Edit::Remove { index });
+        }
+    }
+    /// Adds a label above the others, returns its index.
+    pub fn add_label(&mut self, label: Label) -> usize {
+        let index = self.labels.len();
+        self.apply(Edit::InsertLabel { index, label });
+        index
+    }
+    pub fn set_label(&mut self, index: usize, label: Label) {
+        self.apply(Edit::ReplaceLabel { index, label });
+    }
+    pub fn remove_label(&mut self, index: usize) {
+        self.apply(Edit::RemoveLabel { index });
+    }
+    pub fn translate_label(&mut self, index: usize, offset: (f32, f32)) {
+        let mut label = self.labels[index].clone();
+        label.position = (label.position.0 + offset.0, label.position.1 + offset.1);
+        self.apply(Edit::ReplaceLabel { index, label });
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, Error> {
+        let mut document: Document = match legacy::version(s)? {
+            1 => Document { shapes: legacy::v1(s)?, ..Document::new() },
+            2 => Document { shapes: legacy::v2(s)?, ..Document::new() },
+            // 第 4 版多了文字标签，第 5 版多了图层
+            3..=VERSION => ron::from_str(s)?,
+            version => return Err(Error::UnsupportedVersion(version)),
+        };
+        document.version = VERSION;
+        if document.shapes.last().is_none_or(|shape| !shape.is_empty()) {
+            document.shapes.push(Shape::default());
+        }
+        if document.layers.is_empty() {
+            document.layers = layer::default_layers();
+        }
+        document.check_active_layer();
+        // 图层不存在的图形放进最底层
+        let bottom = document.layers[0].id;
+        for i in 0..document.shapes.len() {
+            if document.layer_index(document.shapes[i].layer).is_none() {
+                document.shapes[i].layer = bottom;
+            }
+        }
+        Ok(document)
+    }
+    pub fn to_ron(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+    Svg(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Parse(e) => write!(f, "invalid document: {e}"),
+            Error::Serialize(e) => write!(f, "cannot serialize document: {e}"),
+            Error::UnsupportedVersion(v) => write!(f, "document version {v} is newer than supported version {VERSION}"),
+            Error::Svg(e) => write!(f, "invalid SVG: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for Error {
+    fn from(e: ron::error::SpannedError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Error::Serialize(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_drawn_finished_and_deleted() {
+        let mut document = Document::new();
+        assert!(!document.new_path(true));
+        for point in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)] {
+            document.push_node(Node::line(point));
+        }
+        assert_eq!(document.current().points().count(), 3);
+        assert!(document.new_path(true));
+        assert_eq!(document.shapes().len(), 2);
+        assert!(document.shapes()[0].fill && document.shapes()[0].closed);
+        assert!(document.current().is_empty());
+
+        // 正在画的路径先被清空，之后才删除画好的路径
+        document.push_node(Node::line((2.0, 2.0)));
+        document.delete_path();
+        assert_eq!(document.shapes().len(), 2);
+        assert!(document.current().is_empty());
+        document.delete_path();
+        assert_eq!(document.shapes().len(), 1);
+        document.delete_path();
+        assert_eq!(document.shapes(), [Shape::default()]);
+        assert!(document.undo());
+        assert_eq!(document.shapes()[0].points().count(), 3);
+    }
+
+    #[test]
+    fn saved_documents_load_back() {
+        let mut document = Document::new();
+        for point in [(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)] {
+            document.push_node(Node::line(point));
+        }
+        document.finish_path(false, false);
+        document.push_node(Node::line((0.25, 0.25)));
+        let path = std::env::temp_dir().join(format!("drawing-round-trip-{}.ron", std::process::id()));
+        document.save(&path).unwrap();
+        let loaded = Document::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut loaded = loaded.unwrap();
+        // 没画完的路径读回来算作画好的，后面接一条新路径
+        assert_eq!(loaded.shapes()[..2], *document.shapes());
+        assert!(loaded.current().is_empty());
+        assert_eq!(loaded.layers(), document.layers());
+        // 没有保存撤销记录
+        assert!(!loaded.undo());
+        assert!(matches!(Document::load(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn version_2_documents_are_migrated() {
+        let v2 = "(version: 2, shapes: [(contours: [[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]], fill: true, closed: true)])";
+        let document = Document::from_ron(v2).unwrap();
+        let shape = &document.shapes()[0];
+        assert_eq!(shape.contours[0].len(), 3);
+        assert!(!shape.has_curves());
+        assert_eq!(Document::from_ron(&document.to_ron().unwrap()).unwrap().shapes(), document.shapes());
+    }
+
+    #[test]
+    fn mirrored_arcs_keep_their_shape() {
+        let mut shape = Shape::from_points(vec![(-1.0, 0.0)], false, false, Style::default());
+        shape.push(Node {
+            point: (1.0, 0.0),
+            segment: Segment::Arc { radii: (1.0, 1.0), rotation: 0.0, large_arc: false, sweep: true },
+        });
+        let before = shape.flatten(0.001);
+        let flip = Affine2::from_scale(Vec2::new(1.0, -1.0));
+        shape.transform(flip);
+        let after = shape.flatten(0.001);
+        for (&a, &b) in before[0].iter().zip(&after[0]) {
+            assert!(flip.transform_point2(a.into()).distance(b.into()) < 1e-4);
+        }
+
+        // 非均匀缩放下圆弧转换为三次曲线
+        shape.transform(Affine2::from_scale(Vec2::new(2.0, 1.0)));
+        assert!(shape.contours[0].iter().skip(1).all(|node| matches!(node.segment, Segment::Cubic(..))));
+        let stretched = shape.flatten(0.001);
+        assert!(stretched[0].iter().all(|&(x, y)| ((x / 2.0).powi(2) + y * y - 1.0).abs() < 1e-2));
+    }
+
+    #[test]
+    fn curves_survive_svg() {
+        let mut document = Document::new();
+        document.push_node(Node::line((0.0, 0.0)));
+        document.push_node(Node { point: (0.5, 0.0), segment: Segment::Quadratic((0.25, 0.5)) });
+        document.push_node(Node { point: (0.5, 0.5), segment: Segment::Cubic((0.6, 0.1), (0.6, 0.4)) });
+        document.push_node(Node {
+            point: (0.0, 0.5),
+            segment: Segment::Arc { radii: (0.25, 0.25), rotation: 0.0, large_arc: false, sweep: true },
+        });
+        document.new_path(false);
+        let svg = svg::export(&document, 100, 100);
+        assert!(svg.contains(" Q ") && svg.contains(" C ") && svg.contains(" A "), "{svg}");
+
+        let shapes = svg::import(&svg, 1.0).unwrap();
+        let segments: Vec<Segment> = shapes[0].contours[0].iter().map(|node| node.segment).collect();
+        assert!(matches!(segments[..], [_, Segment::Quadratic(_), Segment::Cubic(..), Segment::Arc { sweep: true, .. }]));
+    }
+
+    #[test]
+    fn measures() {
+        let square = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        let hole = vec![(0.5, 0.5), (1.5, 0.5), (1.5, 1.5), (0.5, 1.5)];
+        let mut shape = Shape::from_points(square.clone(), true, true, Style::default());
+        shape.contours.push(hole.into_iter().map(Node::line).collect());
+        assert!((shape.area(0.001) - 4.0).abs() < 1e-4);
+        shape.fill_rule = FillRule::EvenOdd;
+        assert!((shape.area(0.001) - 3.0).abs() < 1e-4);
+        assert!((shape.perimeter(0.001) - 12.0).abs() < 1e-4);
+        assert_eq!(shape.bounds(0.001), Some(((0.0, 0.0), (2.0, 2.0))));
+
+        let open = Shape::from_points(square, false, false, Style::default());
+        assert_eq!((open.area(0.001), open.perimeter(0.001)), (0.0, 6.0));
+        assert_eq!(Shape::default().bounds(0.001), None);
+    }
+
+    #[test]
+    fn styles_round_trip() {
+        let style = Style {
+            width: 0.05,
+            fill_color: Color::rgb(1.0, 0.0, 0.0).with_alpha(0.5),
+            stroke_color: Color::rgb(0.0, 0.0, 1.0),
+            opacity: 0.8,
+            ..Style::default()
+        };
+        let mut document = Document::new();
+        document.insert_shapes([Shape::from_points(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], true, true, style)]);
+        let loaded = Document::from_ron(&document.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.shapes()[0].style, style);
+
+        let imported = svg::import(&svg::export(&document, 100, 100), 1.0).unwrap();
+        let imported = imported[0].style;
+        assert_eq!(imported.fill_color.to_string(), "#ff0000");
+        assert_eq!(imported.stroke_color, style.stroke_color);
+        assert_eq!((imported.fill_color.a, imported.opacity), (0.5, 0.8));
+    }
+
+    #[test]
+    fn closing_segment_belongs_to_the_first_node() {
+        let mut shape = Shape::from_points(vec![(0.0, 0.0), (1.0, 0.0)], true, true, Style::default());
+        shape.contours[0][0].segment = Segment::Quadratic((0.5, -1.0));
+        let contour = &shape.flatten(0.01)[0];
+        assert!(contour.len() > 3);
+        assert_ne!(contour.last(), contour.first());
+        assert!(contour.iter().any(|&(_, y)| y < -0.4));
+    }
+
+    #[test]
+    fn labels_are_saved_undone_and_exported() {
+        let mut document = Document::new();
+        let index = document.add_label(Label { align: Align::Center, ..Label::new((0.5, 0.25), "") });
+        document.history_mut().begin_group();
+        for text in ["a", "a<", "a<b"] {
+            document.set_label(index, Label { text: text.into(), ..document.labels()[index].clone() });
+        }
+        document.history_mut().end_group();
+        document.translate_label(index, (0.5, 0.0));
+        assert_eq!(document.labels()[index].position, (1.0, 0.25));
+
+        let loaded = Document::from_ron(&document.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.labels(), document.labels());
+        let svg = svg::export(&document, 100, 100);
+        assert!(svg.contains("text-anchor=\"middle\"") && svg.contains(">a&lt;b</text>"), "{svg}");
+
+        // 整段输入一步撤销
+        assert!(document.undo() && document.undo());
+        assert_eq!(document.labels()[index].text, "");
+        assert!(document.undo());
+        assert!(document.labels().is_empty());
+        assert!(document.redo());
+        assert_eq!(document.labels().len(), 1);
+
+        // 第 3 版文件没有标签
+        let v3 = "(version: 3, shapes: [])";
+        assert!(Document::from_ron(v3).unwrap().labels().is_empty());
+    }
+
+    #[test]
+    fn layers_order_hide_and_lock_shapes() {
+        let triangle = |document: &mut Document, x: f32| {
+            for point in [(x, 0.0), (x + 1.0, 0.0), (x, 1.0)] {
+                document.push_node(Node::line(point));
+            }
+            document.new_path(true);
+        };
+        let mut document = Document::new();
+        triangle(&mut document, 0.0);
+        assert_eq!(document.add_layer("Ink"), 1);
+        triangle(&mut document, 2.0);
+        assert_eq!(document.draw_order(), [0, 1, 2]);
+        assert_eq!((document.layer_of(1), document.layer_of(2)), (1, 1));
+
+        // 下移后墨水层画在最底下，正在画的路径跟着当前图层
+        document.move_layer(1, 0);
+        assert_eq!(document.draw_order(), [1, 2, 0]);
+        assert_eq!(document.active_layer(), 0);
+
+        let hidden = Layer { visible: false, ..document.layers()[0].clone() };
+        document.set_layer(0, hidden);
+        assert_eq!(document.draw_order(), [0]);
+        let svg = svg::export(&document, 100, 100);
+        assert_eq!(svg.matches("<polygon").count(), 1, "{svg}");
+        document.push_node(Node::line((5.0, 5.0)));
+        assert!(document.current().is_empty());
+
+        let locked = Layer { visible: true, locked: true, ..document.layers()[0].clone() };
+        document.set_layer(0, locked);
+        let before = document.shapes()[1].clone();
+        document.translate_shape(1, (1.0, 0.0));
+        document.delete_path();
+        assert_eq!(document.shapes()[1], before);
+        assert!(!document.is_editable(1) && document.is_editable(0));
+
+        let loaded = Document::from_ron(&document.to_ron().unwrap()).unwrap();
+        assert_eq!((loaded.layers(), loaded.active_layer()), (document.layers(), 0));
+        assert_eq!(loaded.draw_order(), [1, 2, 0]);
+
+        assert!(document.undo() && document.undo() && document.undo());
+        assert_eq!(document.layers()[1].name, "Ink");
+        // 删除图层时连同它的图形，撤销后都回来
+        assert!(document.remove_layer(1));
+        assert_eq!((document.shapes().len(), document.layers().len()), (2, 1));
+        assert!(!document.remove_layer(0));
+        assert!(document.undo());
+        assert_eq!((document.shapes().len(), document.layer_of(1)), (3, 1));
+
+        // 第 4 版的图形都在唯一的图层里
+        let v4 = "(version: 4, shapes: [(contours: [[(point: (0.0, 0.0))]], fill: false, closed: false)])";
+        let document = Document::from_ron(v4).unwrap();
+        assert_eq!((document.layers().len(), document.draw_order()), (1, vec![0, 1]));
+    }
+
+    #[test]
+    fn combining_replaces_the_inputs() {
+        let square = |x: f32, y: f32| Shape::from_points(vec![(x, y), (x + 2.0, y), (x + 2.0, y + 2.0), (x, y + 2.0)], false, true, Style::default());
+        let red = Style { fill_color: Color::rgb(1.0, 0.0, 0.0), ..Style::default() };
+        let mut document = Document::new();
+        document.insert_shapes([Shape { style: red, ..square(0.0, 0.0) }, square(5.0, 0.0), square(1.0, 1.0)]);
+
+        // 结果取代最底下的输入，沿用它的样式
+        assert_eq!(document.combine(&[2, 0], Operation::Union, 0.001), Some(0..1));
+        let union = &document.shapes()[0];
+        assert!(union.fill && union.closed && union.style == red);
+        assert!((union.area(0.001) - 7.0).abs() < 1e-4);
+        assert_eq!(document.shapes().len(), 3);
+
+        assert_eq!(document.combine(&[0, 1], Operation::Intersection, 0.001), Some(0..0));
+        assert_eq!(document.shapes().len(), 1);
+        assert!(document.undo());
+        assert_eq!(document.combine(&[1, 1], Operation::Xor, 0.001), None);
+        assert!(document.undo());
+        assert_eq!(document.shapes()[2], square(1.0, 1.0));
+    }
+}
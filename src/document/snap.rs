@@ -0,0 +1,162 @@
+//! Pulling points onto the grid and onto what is already drawn.
+
+use glam::Vec2;
+
+use super::Shape;
+
+/// Angle steps used by `Options::angle_from`.
+pub const ANGLE_STEP: f32 = std::f32::consts::PI / 12.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Vertex,
+    Midpoint,
+    Grid,
+    /// On a multiple of `ANGLE_STEP` around the previous point.
+    Angle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snap {
+    pub point: (f32, f32),
+    pub kind: Kind,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Options {
+    /// How far vertices and midpoints pull the point.
+    pub radius: f32,
+    pub tolerance: f32,
+    /// Spacing of the grid lines, `None` when the grid is off.
+    pub grid: Option<f32>,
+    /// Keeps the direction from this point to a multiple of `ANGLE_STEP`.
+    pub angle_from: Option<(f32, f32)>,
+    /// The vertex being dragged as `(shape, contour, node)`, which must not snap to itself.
+    pub ignore: Option<(usize, usize, usize)>,
+}
+
+/// Finds where `point` should go.
+///
+/// Angles win over vertices within `radius`, which win over segment midpoints within `radius`, which
+/// in turn win over the nearest grid intersection. Only the shapes at `indices` attract the point.
+pub fn snap(shapes: &[Shape], indices: &[usize], point: (f32, f32), options: &Options) -> Option<Snap> {
+    let point = Vec2::from(point);
+    if let Some(from) = options.angle_from.map(Vec2::from).filter(|&from| from != point) {
+        let offset = point - from;
+        let angle = (offset.y.atan2(offset.x) / ANGLE_STEP).round() * ANGLE_STEP;
+        let direction = Vec2::from_angle(angle);
+        return Some(Snap { point: (from + direction * offset.dot(direction)).into(), kind: Kind::Angle });
+    }
+    let nearest = |candidates: Vec<Vec2>, kind| {
+        candidates.into_iter()
+            .map(|candidate| (candidate.distance(point), candidate))
+            .filter(|&(distance, _)| distance <= options.radius)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, candidate)| Snap { point: candidate.into(), kind })
+    };
+    nearest(vertices(shapes, indices, options.ignore), Kind::Vertex)
+        .or_else(|| nearest(midpoints(shapes, indices, options.ignore, options.tolerance), Kind::Midpoint))
+        .or_else(|| options.grid.filter(|&spacing| spacing > 0.0).map(|spacing| Snap {
+            point: ((point / spacing).round() * spacing).into(),
+            kind: Kind::Grid,
+        }))
+}
+
+fn vertices(shapes: &[Shape], indices: &[usize], ignore: Option<(usize, usize, usize)>) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    for &index in indices {
+        let shape = &shapes[index];
+        for (contour, nodes) in shape.contours.iter().enumerate() {
+            let skipped = |node| ignore == Some((index, contour, node));
+            points.extend(nodes.iter().enumerate().filter(|&(node, _)| !skipped(node)).map(|(_, n)| Vec2::from(n.point)));
+        }
+    }
+    points
+}
+
+/// Half way along each segment; those touching the ignored vertex move with it and are left out.
+fn midpoints(shapes: &[Shape], indices: &[usize], ignore: Option<(usize, usize, usize)>, tolerance: f32) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    for &index in indices {
+        let shape = &shapes[index];
+        for (contour, nodes) in shape.contours.iter().enumerate() {
+            let first = if shape.is_contour_closed(contour) { 0 } else { 1 };
+            for node in first..nodes.len() {
+                let previous = (node + nodes.len() - 1) % nodes.len();
+                if ignore.is_some_and(|i| i == (index, contour, node) || i == (index, contour, previous)) {
+                    continue;
+                }
+                let from = Vec2::from(nodes[previous].point);
+                let mut line = vec![from];
+                nodes[node].segment.flatten(from, nodes[node].point.into(), tolerance, &mut line);
+                points.extend(halfway(&line));
+            }
+        }
+    }
+    points
+}
+
+fn halfway(line: &[Vec2]) -> Option<Vec2> {
+    let length: f32 = line.windows(2).map(|s| s[0].distance(s[1])).sum();
+    let mut left = length / 2.0;
+    for s in line.windows(2) {
+        let step = s[0].distance(s[1]);
+        if step >= left && step > 0.0 {
+            return Some(s[0].lerp(s[1], left / step));
+        }
+        left -= step;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Node, Segment, Style};
+
+    fn assert_snap(snap: Option<Snap>, point: (f32, f32), kind: Kind) {
+        let snap = snap.unwrap();
+        assert_eq!(snap.kind, kind);
+        assert!(Vec2::from(snap.point).distance(point.into()) < 1e-4, "{:?} != {point:?}", snap.point);
+    }
+
+    #[test]
+    fn vertices_before_midpoints_before_grid() {
+        let square = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let shapes = [Shape::from_points(square, false, true, Style::default())];
+        let options = Options { radius: 0.1, tolerance: 0.001, grid: Some(0.3), ..Options::default() };
+        let snap = |x, y| snap(&shapes, &[0], (x, y), &options);
+        assert_snap(snap(0.95, 1.02), (1.0, 1.0), Kind::Vertex);
+        assert_snap(snap(0.52, 0.97), (0.5, 1.0), Kind::Midpoint);
+        // 闭合的轮廓最后一条边也有中点
+        assert_snap(snap(0.03, 0.45), (0.0, 0.5), Kind::Midpoint);
+        assert_snap(snap(0.4, 0.35), (0.3, 0.3), Kind::Grid);
+        assert_eq!(super::snap(&shapes, &[0], (0.4, 0.35), &Options { grid: None, ..options }), None);
+        assert_eq!(super::snap(&shapes, &[], (0.95, 1.02), &Options { grid: None, ..options }), None);
+
+        // 拖动的点不吸附到自己和相邻边的中点上
+        let dragging = Options { ignore: Some((0, 0, 2)), grid: None, ..options };
+        assert_eq!(super::snap(&shapes, &[0], (0.98, 0.98), &dragging), None);
+        assert_eq!(super::snap(&shapes, &[0], (1.0, 0.5), &dragging), None);
+        assert_snap(super::snap(&shapes, &[0], (0.5, 0.02), &dragging), (0.5, 0.0), Kind::Midpoint);
+    }
+
+    #[test]
+    fn curve_midpoints_follow_the_curve() {
+        let mut shape = Shape::from_points(vec![(0.0, 0.0)], false, false, Style::default());
+        shape.push(Node { point: (2.0, 0.0), segment: Segment::Quadratic((1.0, 2.0)) });
+        let options = Options { radius: 0.1, tolerance: 0.0001, ..Options::default() };
+        assert_snap(snap(&[shape], &[0], (1.0, 0.95), &options), (1.0, 1.0), Kind::Midpoint);
+    }
+
+    #[test]
+    fn angles_in_steps() {
+        let options = Options { radius: 0.1, angle_from: Some((1.0, 1.0)), grid: Some(1.0), ..Options::default() };
+        assert_snap(snap(&[], &[], (3.0, 1.1), &options), (3.0, 1.0), Kind::Angle);
+        assert_snap(snap(&[], &[], (2.0, 2.05), &options), (2.025, 2.025), Kind::Angle);
+        // 30 度
+        let (sin, cos) = (30f32).to_radians().sin_cos();
+        assert_snap(snap(&[], &[], (1.0 + cos * 2.0, 1.0 + sin * 2.0 + 0.05), &options),
+                    (1.0 + cos * (2.0 + 0.05 * sin), 1.0 + sin * (2.0 + 0.05 * sin)), Kind::Angle);
+    }
+}
@@ -0,0 +1,704 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use glam::{Affine2, Vec2};
+
+use super::{Align, Cap, Color, Contour, Document, Error, FillRule, Join, Node, Segment, Shape, Style};
+
+/// Tolerance for flattening curves when measuring the drawing.
+const TOLERANCE: f32 = 1e-3;
+
+/// Writes every non-empty shape of the visible layers and every label as SVG.
+///
+/// The view box frames the whole drawing, strokes included, with y flipped to point down, and the
+/// image is as large as fits in `width` by `height`. An empty document gets the view of `ui` at
+/// that size, where x spans `-ratio..ratio` and y spans `-1..1`.
+pub fn export(document: &Document, width: u32, height: u32) -> String {
+    let (min, max) = view_box(document, width as f32 / height as f32);
+    let size = max - min;
+    let scale = (width as f32 / size.x).min(height as f32 / size.y);
+    let (width, height) = ((size.x * scale).round().max(1.0), (size.y * scale).round().max(1.0));
+    let mut svg = String::new();
+    writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{width}" height="{height}" viewBox="{} {} {} {}">"#,
+        min.x,
+        -max.y,
+        size.x,
+        size.y,
+    ).unwrap();
+    // 每个可见图层一个组，与 Inkscape 的图层相同
+    let order = document.draw_order();
+    for (index, layer) in document.layers().iter().enumerate().filter(|(_, layer)| layer.visible) {
+        writeln!(
+            svg,
+            r#"  <g inkscape:groupmode="layer" inkscape:label="{}" opacity="{}">"#,
+            escape(&layer.name),
+            layer.opacity,
+        ).unwrap();
+        for &shape in order.iter().filter(|&&shape| document.layer_of(shape) == index) {
+            if !document.shapes()[shape].is_empty() {
+                write_shape(&mut svg, &document.shapes()[shape]);
+            }
+        }
+        writeln!(svg, "  </g>").unwrap();
+    }
+    for label in document.labels() {
+        if label.text.is_empty() {
+            continue;
+        }
+        let anchor = match label.align {
+            Align::Left => "start",
+            Align::Center => "middle",
+            Align::Right => "end",
+        };
+        writeln!(
+            svg,
+            r#"  <text x="{}" y="{}" font-size="{}" fill="{}" fill-opacity="{}" text-anchor="{anchor}">{}</text>"#,
+            label.position.0,
+            -label.position.1,
+            label.size,
+            label.color,
+            label.color.a,
+            escape(&label.text),
+        ).unwrap();
+    }
+    writeln!(svg, "</svg>").unwrap();
+    svg
+}
+
+/// Corners of the area to export, in document coordinates.
+fn view_box(document: &Document, ratio: f32) -> (Vec2, Vec2) {
+    let Some((min, max)) = document.painted_bounds(TOLERANCE) else {
+        return (Vec2::new(-ratio, -1.0), Vec2::new(ratio, 1.0));
+    };
+    let (min, max) = (Vec2::from(min), Vec2::from(max));
+    // 只有一个点时也给个能用的大小
+    (min, max.max(min + TOLERANCE))
+}
+
+fn write_shape(svg: &mut String, shape: &Shape) {
+    let fill = if shape.fill { shape.style.fill_color.to_string() } else { "none".to_string() };
+    let stroke = if shape.style.width > 0.0 { shape.style.stroke_color.to_string() } else { "none".to_string() };
+    let fill_rule = match shape.fill_rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    };
+    let mut flipped = shape.clone();
+    flipped.transform(Affine2::from_scale(Vec2::new(1.0, -1.0)));
+    let shape = &flipped;
+    let contours: Vec<(usize, &Contour)> = shape.contours.iter().enumerate().filter(|(_, c)| !c.is_empty()).collect();
+    let (element, data) = match contours[..] {
+        [(_, contour)] if !shape.has_curves() => {
+            let element = if shape.closed { "polygon" } else { "polyline" };
+            let points = contour.iter().map(|node| point(node.point)).collect::<Vec<_>>();
+            (element, format!(r#"points="{}""#, points.join(" ")))
+        }
+        _ => {
+            let d = contours.iter()
+                .map(|&(i, contour)| path_data(contour, shape.is_contour_closed(i)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            ("path", format!(r#"d="{d}""#))
+        }
+    };
+    let join = match shape.style.join {
+        Join::Miter => "miter",
+        Join::Round => "round",
+        Join::Bevel => "bevel",
+    };
+    let cap = match shape.style.cap {
+        Cap::Butt => "butt",
+        Cap::Square => "square",
+        Cap::Round => "round",
+    };
+    writeln!(
+        svg,
+        r#"    <{element} {data} fill="{fill}" fill-opacity="{}" fill-rule="{fill_rule}" stroke="{stroke}" stroke-opacity="{}" stroke-width="{}" stroke-linejoin="{join}" stroke-linecap="{cap}" stroke-miterlimit="{}" opacity="{}"/>"#,
+        shape.style.fill_color.a,
+        shape.style.stroke_color.a,
+        shape.style.width,
+        shape.style.miter_limit,
+        shape.style.opacity,
+    ).unwrap();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn point((x, y): (f32, f32)) -> String {
+    format!("{x},{y}")
+}
+
+fn path_data(contour: &Contour, closed: bool) -> String {
+    let mut d = format!("M {}", point(contour[0].point));
+    for node in &contour[1..] {
+        d.push(' ');
+        d.push_str(&segment_data(node));
+    }
+    if closed {
+        if contour.len() > 1 && contour[0].segment != Segment::Line {
+            d.push(' ');
+            d.push_str(&segment_data(&contour[0]));
+        }
+        d.push_str(" Z");
+    }
+    d
+}
+
+fn segment_data(node: &Node) -> String {
+    let end = point(node.point);
+    match node.segment {
+        Segment::Line => format!("L {end}"),
+        Segment::Quadratic(c) => format!("Q {} {end}", point(c)),
+        Segment::Cubic(c1, c2) => format!("C {} {} {end}", point(c1), point(c2)),
+        Segment::Arc { radii, rotation, large_arc, sweep } => {
+            format!("A {} {} {rotation} {} {} {end}", radii.0, radii.1, large_arc as u8, sweep as u8)
+        }
+    }
+}
+
+pub fn save(document: &Document, width: u32, height: u32, path: impl AsRef<Path>) -> Result<(), Error> {
+    std::fs::write(path, export(document, width, height))?;
+    Ok(())
+}
+
+/// Reads `<polyline>`, `<polygon>`, `<rect>`, `<line>` and `<path>` elements into shapes.
+///
+/// Curves in path data are kept as curve segments, `transform` attributes of the elements and
+/// their groups are applied, and the result is scaled to fit the view spanning `-ratio..ratio` by `-1..1`.
+pub fn import(svg: &str, ratio: f32) -> Result<Vec<Shape>, Error> {
+    let tree = roxmltree::Document::parse(svg).map_err(|e| Error::Svg(e.to_string()))?;
+    let mut shapes = Vec::new();
+    collect(tree.root_element(), Affine2::IDENTITY, Paint::default(), &mut shapes)?;
+    shapes.retain(|shape| !shape.is_empty());
+    fit(&mut shapes, ratio);
+    Ok(shapes)
+}
+
+pub fn load(path: impl AsRef<Path>, ratio: f32) -> Result<Vec<Shape>, Error> {
+    import(&std::fs::read_to_string(path)?, ratio)
+}
+
+/// Presentation attributes inherited from enclosing groups.
+#[derive(Copy, Clone)]
+struct Paint {
+    fill: Option<bool>,
+    stroke: Option<bool>,
+    fill_color: Option<Color>,
+    fill_opacity: Option<f32>,
+    stroke_color: Option<Color>,
+    stroke_opacity: Option<f32>,
+    opacity: f32,
+    fill_rule: Option<FillRule>,
+    stroke_width: Option<f32>,
+    join: Option<Join>,
+    cap: Option<Cap>,
+    miter_limit: Option<f32>,
+}
+
+impl Default for Paint {
+    fn default() -> Self {
+        Self {
+            fill: None,
+            stroke: None,
+            fill_color: None,
+            fill_opacity: None,
+            stroke_color: None,
+            stroke_opacity: None,
+            opacity: 1.0,
+            fill_rule: None,
+            stroke_width: None,
+            join: None,
+            cap: None,
+            miter_limit: None,
+        }
+    }
+}
+
+impl Paint {
+    fn inherit(self, node: roxmltree::Node) -> Self {
+        let fill = property(node, "fill").map(|fill| fill != "none").or(self.fill);
+        let stroke = property(node, "stroke").map(|stroke| stroke != "none").or(self.stroke);
+        let fill_color = property(node, "fill").and_then(Color::parse).or(self.fill_color);
+        let fill_opacity = property(node, "fill-opacity").and_then(number_prefix).or(self.fill_opacity);
+        let stroke_color = property(node, "stroke").and_then(Color::parse).or(self.stroke_color);
+        let stroke_opacity = property(node, "stroke-opacity").and_then(number_prefix).or(self.stroke_opacity);
+        // opacity 不继承，而是与外层的组相乘
+        let opacity = self.opacity * property(node, "opacity").and_then(number_prefix).unwrap_or(1.0);
+        let fill_rule = property(node, "fill-rule")
+            .map(|rule| if rule == "evenodd" { FillRule::EvenOdd } else { FillRule::NonZero })
+            .or(self.fill_rule);
+        let stroke_width = property(node, "stroke-width").and_then(number_prefix).or(self.stroke_width);
+        let join = property(node, "stroke-linejoin")
+            .map(|join| match join {
+                "round" => Join::Round,
+                "bevel" => Join::Bevel,
+                _ => Join::Miter,
+            })
+            .or(self.join);
+        let cap = property(node, "stroke-linecap")
+            .map(|cap| match cap {
+                "round" => Cap::Round,
+                "square" => Cap::Square,
+                _ => Cap::Butt,
+            })
+            .or(self.cap);
+        let miter_limit = property(node, "stroke-miterlimit").and_then(number_prefix).or(self.miter_limit);
+        Self { fill, stroke, fill_color, fill_opacity, stroke_color, stroke_opacity, opacity, fill_rule, stroke_width, join, cap, miter_limit }
+    }
+}
+
+fn property<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let from_style = node.attribute("style").and_then(|style| {
+        style.split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim())
+    });
+    from_style.or_else(|| node.attribute(name).map(str::trim))
+}
+
+fn collect(node: roxmltree::Node, transform: Affine2, paint: Paint, shapes: &mut Vec<Shape>) -> Result<(), Error> {
+    let transform = match node.attribute("transform") {
+        Some(t) => transform * parse_transform(t)?,
+        None => transform,
+    };
+    let paint = paint.inherit(node);
+    let length = |name| node.attribute(name).and_then(number_prefix).unwrap_or(0.0);
+
+    let mut contours: Vec<(Contour, bool)> = Vec::new();
+    match node.tag_name().name() {
+        "svg" | "g" | "a" => {
+            for child in node.children().filter(roxmltree::Node::is_element) {
+                collect(child, transform, paint, shapes)?;
+            }
+        }
+        tag @ ("polyline" | "polygon") => {
+            let values = Numbers::new(node.attribute("points").unwrap_or("")).collect::<Result<Vec<_>, _>>()?;
+            let points = values.chunks_exact(2).map(|p| Node::line((p[0], p[1]))).collect();
+            contours.push((points, tag == "polygon"));
+        }
+        "rect" => {
+            let (x, y, w, h) = (length("x"), length("y"), length("width"), length("height"));
+            let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+            contours.push((corners.map(Node::line).to_vec(), true));
+        }
+        "line" => {
+            contours.push((vec![Node::line((length("x1"), length("y1"))), Node::line((length("x2"), length("y2")))], false));
+        }
+        "path" => {
+            contours = parse_path(node.attribute("d").unwrap_or(""))?;
+        }
+        _ => {}
+    }
+
+    // SVG 默认不描边，描边时线宽默认为 1 个用户单位，随图形一起缩放
+    let width = if paint.stroke.unwrap_or(false) { paint.stroke_width.unwrap_or(1.0) } else { 0.0 };
+    let style = Style {
+        width,
+        join: paint.join.unwrap_or_default(),
+        cap: paint.cap.unwrap_or_default(),
+        miter_limit: paint.miter_limit.unwrap_or(4.0),
+        fill_color: paint.fill_color.unwrap_or(Color::BLACK).with_alpha(paint.fill_opacity.unwrap_or(1.0).clamp(0.0, 1.0)),
+        stroke_color: paint.stroke_color.unwrap_or(Color::BLACK).with_alpha(paint.stroke_opacity.unwrap_or(1.0).clamp(0.0, 1.0)),
+        opacity: paint.opacity.clamp(0.0, 1.0),
+    };
+    let fill = paint.fill.unwrap_or(true);
+    // 闭合的子路径合成一个可以带洞的图形，未闭合的各自成为一条折线
+    let (closed, open): (Vec<_>, Vec<_>) = contours.into_iter().partition(|(_, closed)| *closed);
+    let first = shapes.len();
+    if !closed.is_empty() {
+        shapes.push(Shape {
+            contours: closed.into_iter().map(|(contour, _)| contour).collect(),
+            fill,
+            fill_rule: paint.fill_rule.unwrap_or_default(),
+            closed: true,
+            style,
+            layer: 0,
+        });
+    }
+    for (contour, _) in open {
+        let fill = fill && contour.len() > 2;
+        shapes.push(Shape { contours: vec![contour], fill, closed: false, style, ..Shape::default() });
+    }
+    for shape in &mut shapes[first..] {
+        shape.transform(transform);
+    }
+    Ok(())
+}
+
+/// Scales and flips SVG user space (y pointing down) so that everything fits in the current view.
+fn fit(shapes: &mut [Shape], ratio: f32) {
+    let mut min = Vec2::splat(f32::INFINITY);
+    let mut max = Vec2::splat(f32::NEG_INFINITY);
+    for &p in shapes.iter().flat_map(Shape::points) {
+        min = min.min(p.into());
+        max = max.max(p.into());
+    }
+    if !min.is_finite() || !max.is_finite() {
+        return;
+    }
+    let size = (max - min).max(Vec2::splat(f32::EPSILON));
+    let scale = (2.0 * ratio / size.x).min(2.0 / size.y) * 0.9;
+    let center = (min + max) / 2.0;
+    let transform = Affine2::from_scale(Vec2::new(scale, -scale)) * Affine2::from_translation(-center);
+    for shape in shapes {
+        shape.transform(transform);
+    }
+}
+
+fn parse_transform(s: &str) -> Result<Affine2, Error> {
+    let mut transform = Affine2::IDENTITY;
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let open = rest.find('(').ok_or_else(|| Error::Svg(format!("invalid transform `{s}`")))?;
+        let close = rest.find(')').ok_or_else(|| Error::Svg(format!("invalid transform `{s}`")))?;
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let args = Numbers::new(&rest[open + 1..close]).collect::<Result<Vec<_>, _>>()?;
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0.0);
+        let next = match (name, args.len()) {
+            ("matrix", 6) => Affine2::from_cols_array(&[arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)]),
+            ("translate", 1 | 2) => Affine2::from_translation(Vec2::new(arg(0), arg(1))),
+            ("scale", 1) => Affine2::from_scale(Vec2::splat(arg(0))),
+            ("scale", 2) => Affine2::from_scale(Vec2::new(arg(0), arg(1))),
+            ("rotate", 1) => Affine2::from_angle(arg(0).to_radians()),
+            ("rotate", 3) => {
+                let pivot = Vec2::new(arg(1), arg(2));
+                Affine2::from_translation(pivot) * Affine2::from_angle(arg(0).to_radians()) * Affine2::from_translation(-pivot)
+            }
+            ("skewX", 1) => Affine2::from_cols_array(&[1.0, 0.0, arg(0).to_radians().tan(), 1.0, 0.0, 0.0]),
+            ("skewY", 1) => Affine2::from_cols_array(&[1.0, arg(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+            _ => return Err(Error::Svg(format!("unsupported transform `{}`", &rest[..=close]))),
+        };
+        transform *= next;
+        rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Ok(transform)
+}
+
+/// Splits path data into subpaths, flagged with whether the subpath was closed.
+fn parse_path(d: &str) -> Result<Vec<(Contour, bool)>, Error> {
+    let mut contours = Vec::new();
+    let mut nodes: Contour = Vec::new();
+    let mut numbers = Numbers::new(d);
+    let mut command = None;
+    let mut current = Vec2::ZERO;
+    let mut start = Vec2::ZERO;
+    // 上一段曲线的控制点，用于 S/T 命令的反射
+    let mut last_control: Option<(u8, Vec2)> = None;
+
+    let finish = |nodes: &mut Contour, contours: &mut Vec<(Contour, bool)>, closed: bool| {
+        if closed && nodes.len() > 1 && nodes.first().map(|n| n.point) == nodes.last().map(|n| n.point) {
+            // 回到起点的那一段就是闭合段
+            let last = nodes.pop().unwrap();
+            nodes[0].segment = last.segment;
+        }
+        if nodes.len() > 1 {
+            contours.push((std::mem::take(nodes), closed));
+        } else {
+            nodes.clear();
+        }
+    };
+
+    loop {
+        match numbers.command() {
+            Some(c) => command = Some(c),
+            None if numbers.at_end() => break,
+            None if command.is_none() => return Err(Error::Svg(format!("path data must start with a command: `{d}`"))),
+            None => {}
+        }
+        let c = command.unwrap();
+        let relative = c.is_ascii_lowercase();
+        let base = if relative { current } else { Vec2::ZERO };
+        let point = |numbers: &mut Numbers| -> Result<Vec2, Error> {
+            Ok(base + Vec2::new(numbers.number()?, numbers.number()?))
+        };
+        let mut control = None;
+        let segment = match c.to_ascii_uppercase() {
+            b'M' => {
+                finish(&mut nodes, &mut contours, false);
+                current = point(&mut numbers)?;
+                start = current;
+                // 紧跟在 M 后面的坐标对被视为 L
+                command = Some(if relative { b'l' } else { b'L' });
+                Segment::Line
+            }
+            b'L' => {
+                current = point(&mut numbers)?;
+                Segment::Line
+            }
+            b'H' => {
+                current.x = numbers.number()? + if relative { current.x } else { 0.0 };
+                Segment::Line
+            }
+            b'V' => {
+                current.y = numbers.number()? + if relative { current.y } else { 0.0 };
+                Segment::Line
+            }
+            b'Z' => {
+                finish(&mut nodes, &mut contours, true);
+                current = start;
+                command = None;
+                Segment::Line
+            }
+            b'Q' | b'T' => {
+                let c1 = if c.eq_ignore_ascii_case(&b'Q') {
+                    point(&mut numbers)?
+                } else {
+                    match last_control {
+                        Some((b'Q', p)) => current * 2.0 - p,
+                        _ => current,
+                    }
+                };
+                current = point(&mut numbers)?;
+                control = Some((b'Q', c1));
+                Segment::Quadratic(c1.into())
+            }
+            b'C' | b'S' => {
+                let c1 = if c.eq_ignore_ascii_case(&b'C') {
+                    point(&mut numbers)?
+                } else {
+                    match last_control {
+                        Some((b'C', p)) => current * 2.0 - p,
+                        _ => current,
+                    }
+                };
+                let c2 = point(&mut numbers)?;
+                current = point(&mut numbers)?;
+                control = Some((b'C', c2));
+                Segment::Cubic(c1.into(), c2.into())
+            }
+            b'A' => {
+                let radii = (numbers.number()?, numbers.number()?);
+                let rotation = numbers.number()?;
+                let large_arc = numbers.flag()?;
+                let sweep = numbers.flag()?;
+                current = point(&mut numbers)?;
+                Segment::Arc { radii, rotation, large_arc, sweep }
+            }
+            _ => return Err(Error::Svg(format!("unsupported path command `{}`", c as char))),
+        };
+        nodes.push(Node { point: current.into(), segment });
+        last_control = control;
+    }
+    finish(&mut nodes, &mut contours, false);
+    Ok(contours)
+}
+
+fn number_prefix(s: &str) -> Option<f32> {
+    Numbers::new(s).next().and_then(Result::ok)
+}
+
+/// Scanner for the SVG number grammar, where separators are optional (`10-5`, `.5.5`).
+struct Numbers<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Numbers<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, pos: 0 }
+    }
+    fn bytes(&self) -> &'a [u8] {
+        self.s.as_bytes()
+    }
+    fn skip_separators(&mut self) {
+        while self.pos < self.s.len() && (self.bytes()[self.pos].is_ascii_whitespace() || self.bytes()[self.pos] == b',') {
+            self.pos += 1;
+        }
+    }
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.s.len()
+    }
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let c = *self.bytes().get(self.pos)?;
+        if c.is_ascii_alphabetic() && c != b'e' && c != b'E' {
+            self.pos += 1;
+            Some(c)
+        } else {
+            None
+        }
+    }
+    fn flag(&mut self) -> Result<bool, Error> {
+        self.skip_separators();
+        let flag = match self.bytes().get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error()),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+    fn number(&mut self) -> Result<f32, Error> {
+        self.skip_separators();
+        let bytes = self.bytes();
+        let start = self.pos;
+        let mut end = start;
+        if matches!(bytes.get(end), Some(b'+' | b'-')) {
+            end += 1;
+        }
+        let mut seen_dot = false;
+        while let Some(&c) = bytes.get(end) {
+            if c.is_ascii_digit() {
+                end += 1;
+            } else if c == b'.' && !seen_dot {
+                seen_dot = true;
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent = end + 1;
+            if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+                exponent += 1;
+            }
+            if bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+                end = exponent;
+                while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+                    end += 1;
+                }
+            }
+        }
+        let value = self.s[start..end].parse().map_err(|_| self.error())?;
+        self.pos = end;
+        Ok(value)
+    }
+    fn error(&self) -> Error {
+        Error::Svg(format!("expected a number at offset {} in `{}`", self.pos, self.s))
+    }
+}
+
+impl Iterator for Numbers<'_> {
+    type Item = Result<f32, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.at_end() {
+            None
+        } else {
+            Some(self.number())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shapes in SVG user space, before `fit`.
+    fn shapes(svg: &str) -> Vec<Shape> {
+        let tree = roxmltree::Document::parse(svg).unwrap();
+        let mut shapes = Vec::new();
+        collect(tree.root_element(), Affine2::IDENTITY, Paint::default(), &mut shapes).unwrap();
+        shapes
+    }
+
+    fn points(contour: &Contour) -> Vec<(f32, f32)> {
+        contour.iter().map(|node| node.point).collect()
+    }
+
+    #[test]
+    fn export_flips_y_into_the_view_box() {
+        let style = Style { width: 0.5, fill_color: Color::rgb(1.0, 0.0, 0.0), stroke_color: Color::rgb(0.0, 0.0, 1.0), ..Style::default() };
+        let mut document = Document::new();
+        document.insert_shapes([
+            Shape::from_points(vec![(0.0, 0.0), (1.0, 0.0), (0.5, 0.75)], true, true, style),
+            Shape::from_points(vec![(-1.5, -1.0), (1.5, 1.0)], false, false, Style { width: 0.0, ..style }),
+        ]);
+        let svg = export(&document, 300, 200);
+        // 视框包住描边，图片按视框的比例缩小到放得下
+        assert!(svg.contains(r#"width="280" height="200" viewBox="-1.75 -1.25 3.5 2.5""#), "{svg}");
+        assert!(svg.contains(r##"<polygon points="0,0 1,0 0.5,-0.75" fill="#ff0000" fill-opacity="1" fill-rule="nonzero" stroke="#0000ff""##), "{svg}");
+        assert!(svg.contains(r#"stroke-width="0.5""#), "{svg}");
+        assert!(svg.contains(r#"<polyline points="-1.5,1 1.5,-1" fill="none" fill-opacity="1" fill-rule="nonzero" stroke="none""#), "{svg}");
+        // 正在画的空路径不导出
+        assert_eq!(svg.matches("points=").count(), 2);
+    }
+
+    #[test]
+    fn view_box_frames_shapes_outside_the_view() {
+        let mut document = Document::new();
+        document.insert_shapes([Shape::from_points(vec![(2.0, 3.0), (6.0, 5.0)], false, false, Style { width: 0.0, ..Style::default() })]);
+        let svg = export(&document, 400, 400);
+        assert!(svg.contains(r#"width="400" height="200" viewBox="2 -5 4 2""#), "{svg}");
+
+        let svg = export(&Document::new(), 300, 200);
+        assert!(svg.contains(r#"width="300" height="200" viewBox="-1.5 -1 3 2""#), "{svg}");
+    }
+
+    #[test]
+    fn relative_and_axis_commands() {
+        let contours = parse_path("m 1 1 l 2 0 v 2 h -2 z M 10 10 H 12 V 12").unwrap();
+        assert_eq!(contours.len(), 2);
+        assert_eq!(points(&contours[0].0), [(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0)]);
+        assert!(contours[0].1);
+        assert_eq!(points(&contours[1].0), [(10.0, 10.0), (12.0, 10.0), (12.0, 12.0)]);
+        assert!(!contours[1].1);
+
+        // 相对坐标从当前点算起，T 和 S 反射上一段的控制点
+        let curves = parse_path("M 0 0 q 1 1 2 0 t 2 0 c 0 1 1 1 1 0 s 1 -1 1 0").unwrap();
+        let nodes = &curves[0].0;
+        assert_eq!(nodes[1], Node { point: (2.0, 0.0), segment: Segment::Quadratic((1.0, 1.0)) });
+        assert_eq!(nodes[2], Node { point: (4.0, 0.0), segment: Segment::Quadratic((3.0, -1.0)) });
+        assert_eq!(nodes[3], Node { point: (5.0, 0.0), segment: Segment::Cubic((4.0, 1.0), (5.0, 1.0)) });
+        assert_eq!(nodes[4], Node { point: (6.0, 0.0), segment: Segment::Cubic((5.0, -1.0), (6.0, -1.0)) });
+    }
+
+    #[test]
+    fn elements_and_paint() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <rect x="1" y="2" width="3" height="4"/>
+            <line x1="0" y1="0" x2="5" y2="5" stroke="red" stroke-width="2"/>
+            <polygon points="0,0 1,0 1,1" fill="none" stroke="blue"/>
+            <polyline points="0 0 1 0 1 1"/>
+        </svg>"#;
+        let shapes = shapes(svg);
+        assert_eq!(shapes.len(), 4);
+        assert_eq!(points(&shapes[0].contours[0]), [(1.0, 2.0), (4.0, 2.0), (4.0, 6.0), (1.0, 6.0)]);
+        assert!(shapes[0].closed && shapes[0].fill);
+        // SVG 默认不描边
+        assert_eq!(shapes[0].style.width, 0.0);
+
+        assert!(!shapes[1].closed && !shapes[1].fill);
+        assert_eq!((shapes[1].style.width, shapes[1].style.stroke_color.to_string().as_str()), (2.0, "#ff0000"));
+
+        assert!(shapes[2].closed && !shapes[2].fill);
+        assert_eq!(shapes[2].style.width, 1.0);
+        assert!(!shapes[3].closed && shapes[3].fill);
+    }
+
+    #[test]
+    fn nested_transforms() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <g transform="translate(10 0)">
+                <g transform="scale(2)">
+                    <line x1="1" y1="1" x2="2" y2="1" transform="rotate(90)"/>
+                </g>
+            </g>
+        </svg>"#;
+        let shapes = shapes(svg);
+        let expected = [(8.0, 2.0), (8.0, 4.0)];
+        for (p, e) in shapes[0].points().zip(expected) {
+            assert!(Vec2::from(*p).distance(e.into()) < 1e-5, "{p:?}");
+        }
+
+        // 导入后缩放到视图里并翻转 y 轴
+        let imported = import(svg, 1.0).unwrap();
+        let points: Vec<_> = imported[0].points().copied().collect();
+        assert!((points[0].1 - 0.9).abs() < 1e-5 && (points[1].1 + 0.9).abs() < 1e-5, "{points:?}");
+    }
+
+    #[test]
+    fn malformed_path_data() {
+        for d in ["M 0 0 L 1", "1 2 3 4", "M 0 0 X 1 1", "M 0 0 A 1 1 0 2 0 1 1"] {
+            let svg = format!(r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="{d}"/></svg>"#);
+            assert!(matches!(import(&svg, 1.0), Err(Error::Svg(_))), "{d}");
+        }
+        assert!(matches!(import("<svg", 1.0), Err(Error::Svg(_))));
+    }
+}
@@ -0,0 +1,156 @@
+//! Moving, scaling and rotating whole shapes with the handles of their bounding box.
+
+use glam::{Affine2, Vec2};
+
+use super::snap::ANGLE_STEP;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Handle {
+    /// Anywhere on the shape, moves it.
+    Body,
+    /// A corner or the middle of an edge, given as fractions of the box from its lower left corner;
+    /// scales away from the opposite corner or edge.
+    Scale(Vec2),
+    /// Above the top edge, turns the shape around the pivot.
+    Rotate,
+    /// The point rotations turn around.
+    Pivot,
+}
+
+/// The bounding box of a selected shape and the point it rotates around.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub pivot: Vec2,
+}
+
+impl Frame {
+    /// A frame around `bounds` that rotates around its centre.
+    pub fn new((min, max): ((f32, f32), (f32, f32))) -> Self {
+        let (min, max) = (Vec2::from(min), Vec2::from(max));
+        Self { min, max, pivot: (min + max) / 2.0 }
+    }
+    /// Counterclockwise from the lower left corner.
+    pub fn corners(&self) -> [Vec2; 4] {
+        [self.min, Vec2::new(self.max.x, self.min.y), self.max, Vec2::new(self.min.x, self.max.y)]
+    }
+    fn at(&self, fraction: Vec2) -> Vec2 {
+        self.min + (self.max - self.min) * fraction
+    }
+    /// Where every handle but `Body` is; the rotation handle sits `distance` above the top edge.
+    pub fn handles(&self, distance: f32) -> Vec<(Handle, Vec2)> {
+        let mut handles: Vec<(Handle, Vec2)> = [(0.0, 0.0), (0.5, 0.0), (1.0, 0.0), (1.0, 0.5), (1.0, 1.0), (0.5, 1.0), (0.0, 1.0), (0.0, 0.5)]
+            .into_iter()
+            .map(|fraction| (Handle::Scale(fraction.into()), self.at(fraction.into())))
+            .collect();
+        handles.push((Handle::Rotate, self.at(Vec2::new(0.5, 1.0)) + Vec2::new(0.0, distance)));
+        handles.push((Handle::Pivot, self.pivot));
+        handles
+    }
+    /// The handle within `radius` of `point`; the pivot wins over the others, which may overlap it
+    /// on small shapes.
+    pub fn hit(&self, point: (f32, f32), radius: f32, distance: f32) -> Option<Handle> {
+        let point = Vec2::from(point);
+        self.handles(distance).into_iter().rev()
+            .find(|&(_, position)| position.distance(point) <= radius)
+            .map(|(handle, _)| handle)
+    }
+    /// What dragging `handle` from `from` to `to` does to the shape. `uniform` keeps the proportions
+    /// when scaling and `snap_angle` turns in steps of `ANGLE_STEP`; dragging the pivot does nothing.
+    pub fn drag(&self, handle: Handle, from: (f32, f32), to: (f32, f32), uniform: bool, snap_angle: bool) -> Affine2 {
+        let (from, to) = (Vec2::from(from), Vec2::from(to));
+        match handle {
+            Handle::Body => Affine2::from_translation(to - from),
+            Handle::Pivot => Affine2::IDENTITY,
+            Handle::Rotate => {
+                let (a, b) = (from - self.pivot, to - self.pivot);
+                let mut angle = b.y.atan2(b.x) - a.y.atan2(a.x);
+                if snap_angle {
+                    angle = (angle / ANGLE_STEP).round() * ANGLE_STEP;
+                }
+                around(self.pivot, Affine2::from_angle(angle))
+            }
+            Handle::Scale(fraction) => {
+                let anchor = self.at(Vec2::ONE - fraction);
+                let (reach, moved) = (self.at(fraction) - anchor, to - anchor);
+                // 边上的手柄只缩放一个方向，宽或高为零的方向不缩放
+                let factor = |reach: f32, moved: f32, free: bool| {
+                    if free && reach.abs() > f32::EPSILON { moved / reach } else { 1.0 }
+                };
+                let sx = factor(reach.x, moved.x, fraction.x != 0.5);
+                let sy = factor(reach.y, moved.y, fraction.y != 0.5);
+                let scale = match (uniform, fraction.x != 0.5 && fraction.y != 0.5) {
+                    (false, _) => Vec2::new(sx, sy),
+                    // 角上的手柄按投影到对角线的长度等比缩放
+                    (true, true) => Vec2::splat(moved.dot(reach) / reach.length_squared().max(f32::EPSILON)),
+                    (true, false) => Vec2::splat(if fraction.x != 0.5 { sx } else { sy }),
+                };
+                around(anchor, Affine2::from_scale(scale))
+            }
+        }
+    }
+}
+
+fn around(point: Vec2, transform: Affine2) -> Affine2 {
+    Affine2::from_translation(point) * transform * Affine2::from_translation(-point)
+}
+
+/// The numbers shown while dragging `handle`, for `transform` as made by `Frame::drag`.
+pub fn describe(handle: Handle, transform: Affine2, pivot: Vec2) -> String {
+    let m = transform.matrix2;
+    match handle {
+        Handle::Body => format!("move {:.3}, {:.3}", transform.translation.x, transform.translation.y),
+        Handle::Pivot => format!("pivot {:.3}, {:.3}", pivot.x, pivot.y),
+        Handle::Rotate => format!("rotate {:.1}°", m.x_axis.y.atan2(m.x_axis.x).to_degrees()),
+        Handle::Scale(_) if m.x_axis.x == m.y_axis.y => format!("scale {:.1}%", m.x_axis.x * 100.0),
+        Handle::Scale(_) => format!("scale {:.1}% × {:.1}%", m.x_axis.x * 100.0, m.y_axis.y * 100.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_maps(transform: Affine2, from: (f32, f32), to: (f32, f32)) {
+        let mapped = transform.transform_point2(from.into());
+        assert!(mapped.distance(to.into()) < 1e-5, "{mapped} != {to:?}");
+    }
+
+    #[test]
+    fn handles_scale_away_from_the_opposite_side() {
+        let frame = Frame::new(((0.0, 0.0), (2.0, 1.0)));
+        assert_eq!(frame.hit((2.05, 1.0), 0.1, 0.5), Some(Handle::Scale(Vec2::ONE)));
+        assert_eq!(frame.hit((1.0, 1.5), 0.1, 0.5), Some(Handle::Rotate));
+        assert_eq!(frame.hit((1.0, 0.5), 0.1, 0.5), Some(Handle::Pivot));
+        assert_eq!(frame.hit((1.5, 0.5), 0.1, 0.5), None);
+
+        let corner = Handle::Scale(Vec2::ONE);
+        let free = frame.drag(corner, (2.0, 1.0), (4.0, 3.0), false, false);
+        assert_maps(free, (0.0, 0.0), (0.0, 0.0));
+        assert_maps(free, (2.0, 1.0), (4.0, 3.0));
+        assert_eq!(describe(corner, free, frame.pivot), "scale 200.0% × 300.0%");
+        let uniform = frame.drag(corner, (2.0, 1.0), (4.0, 3.0), true, false);
+        assert_eq!(describe(corner, uniform, frame.pivot), "scale 220.0%");
+
+        // 左边的手柄以右边为准，只改变宽度
+        let edge = frame.drag(Handle::Scale(Vec2::new(0.0, 0.5)), (0.0, 0.5), (1.0, 0.7), false, false);
+        assert_maps(edge, (0.0, 1.0), (1.0, 1.0));
+        assert_maps(edge, (2.0, 0.0), (2.0, 0.0));
+
+        // 高度为零时不会除以零
+        let flat = Frame::new(((0.0, 0.0), (2.0, 0.0)));
+        let scaled = flat.drag(Handle::Scale(Vec2::ONE), (2.0, 0.0), (3.0, 1.0), false, false);
+        assert_maps(scaled, (2.0, 0.0), (3.0, 0.0));
+    }
+
+    #[test]
+    fn rotation_turns_around_the_pivot() {
+        let frame = Frame { pivot: Vec2::ZERO, ..Frame::new(((0.0, 0.0), (2.0, 2.0))) };
+        let turn = frame.drag(Handle::Rotate, (1.0, 0.0), (0.1, 1.0), false, true);
+        assert_maps(turn, (2.0, 0.0), (0.0, 2.0));
+        assert_eq!(describe(Handle::Rotate, turn, frame.pivot), "rotate 90.0°");
+        let free = frame.drag(Handle::Rotate, (1.0, 0.0), (0.1, 1.0), false, false);
+        assert_maps(free, (0.0, 0.0), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,387 @@
+//! Finding a font for the text layer on whatever system the editor runs on.
+//!
+//! A font file from `DRAWING_FONT` wins, then an installed font of the family in `DRAWING_FONT_FAMILY`.
+//! Without either, installed fonts covering Chinese are looked for, and the built-in font is used when
+//! none is found. What the installed fonts are called is cached in `drawing/fonts.ron` in the cache
+//! directory of the platform, so that only new or changed files are read on later starts.
+
+use std::{collections::{HashMap, HashSet}, fmt, fs::{self, File}, io::{self, Read, Seek, SeekFrom}, path::{Path, PathBuf}, sync::OnceLock, time::SystemTime};
+
+use glyph_brush::ab_glyph::{Font as _, FontArc, FontVec};
+use serde::{Deserialize, Serialize};
+
+pub const PATH_VAR: &str = "DRAWING_FONT";
+pub const FAMILY_VAR: &str = "DRAWING_FONT_FAMILY";
+
+/// Always available, but without CJK glyphs.
+const FALLBACK: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+/// Families tried in order when none is configured, all covering CJK.
+const DEFAULT_FAMILIES: &[&str] = &[
+    "Microsoft YaHei",
+    "PingFang SC",
+    "Noto Sans CJK SC",
+    "Noto Sans SC",
+    "Source Han Sans SC",
+    "WenQuanYi Micro Hei",
+    "Droid Sans Fallback",
+];
+const EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+/// How deep font directories are searched.
+const MAX_DEPTH: usize = 4;
+/// Tables needed to tell the family, weight and style of a face, sorted by tag.
+const TABLES: [&[u8; 4]; 5] = [b"OS/2", b"head", b"hhea", b"maxp", b"name"];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub path: Option<PathBuf>,
+    pub family: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            path: std::env::var_os(PATH_VAR).filter(|path| !path.is_empty()).map(PathBuf::from),
+            family: std::env::var(FAMILY_VAR).ok().filter(|family| !family.trim().is_empty()),
+        }
+    }
+}
+
+pub struct Font {
+    pub font: FontArc,
+    /// Where the font came from, for messages.
+    pub source: String,
+}
+
+impl Font {
+    pub fn fallback() -> Self {
+        Self {
+            font: FontArc::try_from_slice(FALLBACK).expect("the built-in font is valid"),
+            source: "built-in DejaVu Sans".to_owned(),
+        }
+    }
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        if let Some(path) = &config.path {
+            return Self::from_file(path, 0);
+        }
+        let families = match &config.family {
+            Some(family) => vec![family.as_str()],
+            None => DEFAULT_FAMILIES.to_vec(),
+        };
+        let cache_path = cache_path();
+        let mut cache = cache_path.as_deref().map(Cache::load).unwrap_or_default();
+        let found = find_family(&system_dirs(), &families, &mut cache);
+        if let (Some(path), true) = (&cache_path, cache.changed) {
+            cache.save(path);
+        }
+        let (path, index) = found.ok_or_else(|| Error::NotFound(families.join(", ")))?;
+        Self::from_file(&path, index)
+    }
+    /// Like `load`, but reports what went wrong and falls back to the built-in font.
+    pub fn load_or_fallback(config: &Config) -> Self {
+        match Self::load(config) {
+            Ok(font) => {
+                if !font.has_glyph('中') {
+                    eprintln!("{} has no CJK glyphs", font.source);
+                }
+                font
+            }
+            Err(e) => {
+                eprintln!("{e}, using the built-in font, which has no CJK glyphs");
+                Self::fallback()
+            }
+        }
+    }
+    /// Loads face `index` of a font file, which is 0 unless the file is a collection.
+    pub fn from_file(path: &Path, index: u32) -> Result<Self, Error> {
+        let data = fs::read(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+        let font = FontVec::try_from_vec_and_index(data, index).map_err(|_| Error::Invalid(path.to_owned()))?;
+        Ok(Self { font: FontArc::new(font), source: path.display().to_string() })
+    }
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph_id(c).0 != 0
+    }
+}
+
+/// The font configured in the environment, loaded once and shared by every text layer.
+pub fn shared() -> FontArc {
+    static FONT: OnceLock<FontArc> = OnceLock::new();
+    FONT.get_or_init(|| Font::load_or_fallback(&Config::from_env()).font).clone()
+}
+
+/// Standard font directories of Linux, macOS and Windows that exist here.
+fn system_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut dirs: Vec<PathBuf> = ["/usr/share/fonts", "/usr/local/share/fonts", "/System/Library/Fonts", "/Library/Fonts"]
+        .into_iter().map(PathBuf::from).collect();
+    if let Some(data) = std::env::var_os("XDG_DATA_HOME") {
+        dirs.push(PathBuf::from(data).join("fonts"));
+    }
+    if let Some(home) = home {
+        dirs.extend([".local/share/fonts", ".fonts", "Library/Fonts"].map(|dir| home.join(dir)));
+    }
+    if let Some(windows) = std::env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windows).join("Fonts"));
+    }
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+    }
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+/// Finds the face of the earliest of `families` among the fonts under `dirs`, ignoring case and
+/// preferring regular faces over bold or italic ones.
+fn find_family(dirs: &[PathBuf], families: &[&str], cache: &mut Cache) -> Option<(PathBuf, u32)> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        font_files(dir, MAX_DEPTH, &mut files);
+    }
+    let mut best: Option<((usize, bool), PathBuf, u32)> = None;
+    for path in &files {
+        for (index, face) in cache.faces(path).iter().enumerate() {
+            let Some((names, regular)) = face else {
+                continue;
+            };
+            let Some(rank) = names.iter()
+                .filter_map(|name| families.iter().position(|family| family.eq_ignore_ascii_case(name)))
+                .min() else {
+                continue;
+            };
+            let rank = (rank, !regular);
+            if best.as_ref().is_none_or(|best| rank < best.0) {
+                best = Some((rank, path.clone(), index as u32));
+            }
+        }
+    }
+    // 删掉已经不在的文件
+    let count = cache.files.len();
+    let files: HashSet<PathBuf> = files.into_iter().collect();
+    cache.files.retain(|path, _| files.contains(path));
+    cache.changed |= cache.files.len() != count;
+    best.map(|(_, path, index)| (path, index))
+}
+
+/// Family names of a face and whether it is the regular one, see [`describe`].
+type Names = (Vec<String>, bool);
+
+/// [`Names`] of each face of the font files seen so far.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    files: HashMap<PathBuf, Entry>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    faces: Vec<Option<Names>>,
+}
+
+impl Cache {
+    fn load(path: &Path) -> Self {
+        // 缓存坏了就重新扫描
+        fs::read_to_string(path).ok().and_then(|s| ron::from_str(&s).ok()).unwrap_or_default()
+    }
+    fn save(&self, path: &Path) {
+        // 缓存只为加快启动，写不了也不影响使用
+        let Ok(s) = ron::to_string(self) else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, s);
+    }
+    /// The faces of a font file, read again only when its size or modification time changed.
+    /// Files that cannot be read have none.
+    fn faces(&mut self, path: &Path) -> &[Option<Names>] {
+        let Ok(metadata) = fs::metadata(path) else {
+            return &[];
+        };
+        let (modified, len) = (metadata.modified().ok(), metadata.len());
+        let fresh = self.files.get(path).is_some_and(|entry| entry.modified == modified && entry.len == len);
+        if !fresh {
+            let faces = scan(path).unwrap_or_default();
+            self.files.insert(path.to_owned(), Entry { modified, len, faces });
+            self.changed = true;
+        }
+        &self.files[path].faces
+    }
+}
+
+/// `drawing/fonts.ron` in the cache directory of the platform.
+fn cache_path() -> Option<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        env("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library/Caches"))
+    } else {
+        env("XDG_CACHE_HOME").or_else(|| env("HOME").map(|home| home.join(".cache")))
+    };
+    dir.map(|dir| dir.join("drawing").join("fonts.ron"))
+}
+
+/// Describes every face of a font file from the few tables that matter, without reading the glyphs.
+fn scan(path: &Path) -> Option<Vec<Option<Names>>> {
+    let mut file = FontFile::open(path)?;
+    let header = file.read(0, 12)?;
+    let offsets = if &header[..4] == b"ttcf" {
+        let count = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+        file.read(12, count * 4)?.chunks(4).map(|b| u32::from_be_bytes(b.try_into().unwrap())).collect()
+    } else {
+        vec![0]
+    };
+    Some(offsets.into_iter()
+        .map(|offset| describe(&file.face(offset)?, 0))
+        .collect())
+}
+
+struct FontFile {
+    file: File,
+    len: u64,
+}
+
+impl FontFile {
+    fn open(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let len = file.metadata().ok()?.len();
+        Some(Self { file, len })
+    }
+    fn read(&mut self, offset: u64, size: usize) -> Option<Vec<u8>> {
+        // 长度来自文件内容，先确认没有越过文件末尾再分配
+        if offset.checked_add(size as u64)? > self.len {
+            return None;
+        }
+        self.file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut buffer = vec![0; size];
+        self.file.read_exact(&mut buffer).ok()?;
+        Some(buffer)
+    }
+    /// A font of its own made of the `TABLES` of the face whose table directory is at `offset`.
+    fn face(&mut self, offset: u32) -> Option<Vec<u8>> {
+        let header = self.read(offset as u64, 12)?;
+        let count = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let records = self.read(offset as u64 + 12, count * 16)?;
+        let mut tables: Vec<(&[u8], u32, u32)> = records.chunks(16)
+            .filter(|record| TABLES.iter().any(|tag| tag[..] == record[..4]))
+            .map(|record| {
+                let at = |i: usize| u32::from_be_bytes(record[i..i + 4].try_into().unwrap());
+                (&record[..4], at(8), at(12))
+            })
+            .collect();
+        tables.sort_by_key(|&(tag, ..)| tag);
+        // 表目录后面依次放各表的数据，按四字节对齐；校验和不检查，填零
+        let mut font = header[..4].to_vec();
+        font.extend((tables.len() as u16).to_be_bytes());
+        font.extend([0; 6]);
+        let mut data = Vec::new();
+        let start = 12 + 16 * tables.len();
+        for (tag, offset, len) in tables {
+            font.extend(tag);
+            font.extend([0; 4]);
+            font.extend(((start + data.len()) as u32).to_be_bytes());
+            font.extend(len.to_be_bytes());
+            data.extend(self.read(offset as u64, len as usize)?);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        font.extend(data);
+        Some(font)
+    }
+}
+
+fn font_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|entry| Some(entry.ok()?.path())).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if depth > 0 {
+                font_files(&path, depth - 1, files);
+            }
+        } else if path.extension().and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.iter().any(|x| x.eq_ignore_ascii_case(e))) {
+            files.push(path);
+        }
+    }
+}
+
+/// Family names of a face in every language the font lists, e.g. both `Microsoft YaHei` and `微软雅黑`,
+/// and whether the face is the regular one.
+fn describe(data: &[u8], index: u32) -> Option<Names> {
+    let face = ttf_parser::Face::parse(data, index).ok()?;
+    let mut names: Vec<String> = face.names().into_iter()
+        .filter(|name| [ttf_parser::name_id::FAMILY, ttf_parser::name_id::TYPOGRAPHIC_FAMILY].contains(&name.name_id))
+        .filter_map(|name| name.to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    // 很多字体没有设置 REGULAR 标志，按字重和宽度判断
+    let regular = !face.is_italic() && face.weight() == ttf_parser::Weight::Normal && face.width() == ttf_parser::Width::Normal;
+    Some((names, regular))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Invalid(PathBuf),
+    NotFound(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "cannot read font {}: {e}", path.display()),
+            Error::Invalid(path) => write!(f, "{} is not a font", path.display()),
+            Error::NotFound(families) => write!(f, "no installed font of the families {families}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_font() {
+        let font = Font::fallback();
+        assert!(font.has_glyph('A'));
+        assert!(!font.has_glyph('中'));
+        assert_eq!(describe(FALLBACK, 0), Some((vec!["DejaVu Sans".to_owned()], true)));
+    }
+
+    #[test]
+    fn finds_families_in_directories() {
+        let dir = std::env::temp_dir().join(format!("drawing-fonts-{}", std::process::id()));
+        fs::create_dir_all(dir.join("truetype")).unwrap();
+        fs::write(dir.join("truetype/Sans.TTF"), FALLBACK).unwrap();
+        fs::write(dir.join("broken.ttf"), b"not a font").unwrap();
+
+        let dirs = [dir.clone()];
+        let mut cache = Cache::default();
+        assert_eq!(find_family(&dirs, &["Missing", "dejavu sans"], &mut cache), Some((dir.join("truetype/Sans.TTF"), 0)));
+        assert_eq!(cache.files[&dir.join("truetype/Sans.TTF")].faces, [describe(FALLBACK, 0)]);
+        assert!(cache.files[&dir.join("broken.ttf")].faces.is_empty());
+
+        // 第二次只看缓存，删掉的文件也从缓存里去掉
+        let mut cache: Cache = ron::from_str(&ron::to_string(&cache).unwrap()).unwrap();
+        fs::remove_file(dir.join("broken.ttf")).unwrap();
+        assert_eq!(find_family(&dirs, &["Missing"], &mut cache), None);
+        assert!(cache.changed && cache.files.len() == 1);
+        cache.changed = false;
+        assert_eq!(find_family(&dirs, &["DejaVu Sans"], &mut cache), Some((dir.join("truetype/Sans.TTF"), 0)));
+        assert!(!cache.changed);
+        fs::write(dir.join("broken.ttf"), b"not a font").unwrap();
+        assert!(matches!(Font::from_file(&dir.join("broken.ttf"), 0), Err(Error::Invalid(_))));
+        assert!(matches!(Font::from_file(&dir.join("missing.ttf"), 0), Err(Error::Io(..))));
+        let config = Config { path: Some(dir.join("truetype/Sans.TTF")), family: Some("Missing".into()) };
+        assert!(Font::load(&config).is_ok_and(|font| font.has_glyph('A')));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+//! Union, intersection, difference and exclusive or of polygons given as closed contours.
+//!
+//! Every edge is cut wherever it crosses or touches another edge, and vertices closer than a small
+//! tolerance are merged, so crossings, T-junctions and overlapping edges all turn into shared
+//! segments. For each segment the winding numbers of both polygons on its two sides decide whether
+//! it bounds the result; the kept segments are linked into contours with the inside on their left.
+
+use std::collections::{BTreeMap, HashMap};
+
+use glam::Vec2;
+
+use super::fill::{winding, FillRule};
+use super::triangulate::signed_area;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// The first polygon without the second.
+    Difference,
+    /// What exactly one of the polygons covers.
+    Xor,
+}
+
+impl Operation {
+    fn keeps(self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+            Operation::Xor => a != b,
+        }
+    }
+}
+
+/// Combines polygon `a`, filled by `a_rule`, with polygon `b`, filled by `b_rule`.
+///
+/// The result never overlaps itself: outlines run counter-clockwise and holes clockwise, so it looks
+/// the same under both fill rules.
+pub fn boolean(a: &[Vec<(f32, f32)>], a_rule: FillRule, b: &[Vec<(f32, f32)>], b_rule: FillRule,
+               operation: Operation) -> Vec<Vec<(f32, f32)>> {
+    clip([a, b], |wind| operation.keeps(a_rule.is_inside(wind[0]), b_rule.is_inside(wind[1])))
+}
+
+/// The area `contours` cover under `rule`, as contours that neither cross nor overlap, see `boolean`.
+pub fn simplify(contours: &[Vec<(f32, f32)>], rule: FillRule) -> Vec<Vec<(f32, f32)>> {
+    boolean(contours, rule, &[], FillRule::NonZero, Operation::Union)
+}
+
+/// The outline of where `inside` holds for the winding numbers around both operands.
+pub(super) fn clip(operands: [&[Vec<(f32, f32)>]; 2], inside: impl Fn([i32; 2]) -> bool) -> Vec<Vec<(f32, f32)>> {
+    let mut edges = Vec::new();
+    for (operand, contours) in operands.into_iter().enumerate() {
+        for contour in contours.iter().filter(|contour| contour.len() >= 3) {
+            for i in 0..contour.len() {
+                let (from, to) = (Vec2::from(contour[i]), Vec2::from(contour[(i + 1) % contour.len()]));
+                if from != to {
+                    edges.push(Edge { from, to, operand });
+                }
+            }
+        }
+    }
+    let Some(extent) = edges.iter().flat_map(|edge| [edge.from, edge.to]).map(|p| p.abs().max_element()).reduce(f32::max) else {
+        return Vec::new();
+    };
+    let epsilon = extent.max(1.0) * 1e-5;
+
+    let segments = split(&edges, epsilon);
+    let mut boundary = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let right = winding_beside(&segments, i);
+        let left = [right[0] + segment.count[0], right[1] + segment.count[1]];
+        match (inside(left), inside(right)) {
+            (true, false) => boundary.push((segment.from, segment.to)),
+            (false, true) => boundary.push((segment.to, segment.from)),
+            _ => {}
+        }
+    }
+    link(boundary, epsilon)
+}
+
+/// Groups contours as made by `boolean` into outlines, each followed by the holes inside it.
+pub fn split_outlines(contours: Vec<Vec<(f32, f32)>>) -> Vec<Vec<Vec<(f32, f32)>>> {
+    let area = |contour: &[(f32, f32)]| signed_area(contour.iter().map(|&p| Vec2::from(p)));
+    let (mut outlines, holes): (Vec<_>, Vec<_>) = contours.into_iter().partition(|contour| area(contour) > 0.0);
+    // 洞属于包含它的最小外轮廓
+    outlines.sort_by(|a, b| area(a).total_cmp(&area(b)));
+    let mut groups: Vec<Vec<Vec<(f32, f32)>>> = outlines.into_iter().map(|outline| vec![outline]).collect();
+    for hole in holes {
+        let probe = (Vec2::from(hole[0]) + Vec2::from(hole[1])) / 2.0;
+        if let Some(group) = groups.iter_mut().find(|group| winding([group[0].as_slice()], probe) != 0) {
+            group.push(hole);
+        }
+    }
+    groups
+}
+
+struct Edge {
+    from: Vec2,
+    to: Vec2,
+    operand: usize,
+}
+
+/// A piece of edge between merged vertices, running from the lower to the higher vertex index, with
+/// how many times each polygon runs along it in that direction minus in the other one.
+struct Segment {
+    from: Vec2,
+    to: Vec2,
+    count: [i32; 2],
+}
+
+fn split(edges: &[Edge], epsilon: f32) -> Vec<Segment> {
+    let mut cuts: Vec<Vec<Vec2>> = edges.iter().map(|edge| vec![edge.from, edge.to]).collect();
+    for i in 0..edges.len() {
+        let (p, q) = (edges[i].from, edges[i].to);
+        for j in i + 1..edges.len() {
+            let (r, s) = (edges[j].from, edges[j].to);
+            if p.max(q).cmplt(r.min(s) - epsilon).any() || r.max(s).cmplt(p.min(q) - epsilon).any() {
+                continue;
+            }
+            // 端点落在另一条边上：T 形接头以及重叠的边
+            for (edge, (a, b), point) in [(i, (p, q), r), (i, (p, q), s), (j, (r, s), p), (j, (r, s), q)] {
+                if lies_inside(a, b, point, epsilon) {
+                    cuts[edge].push(point);
+                }
+            }
+            if let Some(point) = crossing(p, q, r, s) {
+                cuts[i].push(point);
+                cuts[j].push(point);
+            }
+        }
+    }
+
+    let mut vertices = Vertices::new(epsilon);
+    let mut segments: BTreeMap<(usize, usize), [i32; 2]> = BTreeMap::new();
+    for (edge, mut points) in edges.iter().zip(cuts) {
+        let direction = edge.to - edge.from;
+        points.sort_by(|a, b| (*a - edge.from).dot(direction).total_cmp(&(*b - edge.from).dot(direction)));
+        let ids: Vec<usize> = points.into_iter().map(|point| vertices.id(point)).collect();
+        for pair in ids.windows(2).filter(|pair| pair[0] != pair[1]) {
+            let (key, sign) = if pair[0] < pair[1] { ((pair[0], pair[1]), 1) } else { ((pair[1], pair[0]), -1) };
+            segments.entry(key).or_default()[edge.operand] += sign;
+        }
+    }
+    // 方向相反的重边互相抵消
+    segments.into_iter()
+        .filter(|(_, count)| *count != [0, 0])
+        .map(|((from, to), count)| Segment { from: vertices.points[from], to: vertices.points[to], count })
+        .collect()
+}
+
+/// Whether `point` lies on the segment from `a` to `b` away from its ends.
+fn lies_inside(a: Vec2, b: Vec2, point: Vec2, epsilon: f32) -> bool {
+    let ab = b - a;
+    let t = (point - a).dot(ab) / ab.length_squared();
+    t > 0.0 && t < 1.0 && (a + ab * t).distance(point) <= epsilon
+        && point.distance(a) > epsilon && point.distance(b) > epsilon
+}
+
+/// Where the segments from `p` to `q` and from `r` to `s` properly cross.
+fn crossing(p: Vec2, q: Vec2, r: Vec2, s: Vec2) -> Option<Vec2> {
+    let (d1, d2) = (q - p, s - r);
+    let denominator = d1.perp_dot(d2);
+    if denominator.abs() <= f32::EPSILON * d1.length() * d2.length() {
+        return None;
+    }
+    let t = (r - p).perp_dot(d2) / denominator;
+    let u = (r - p).perp_dot(d1) / denominator;
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then(|| p + d1 * t)
+}
+
+/// Points merged when they are within `epsilon`, found through a grid of that size.
+struct Vertices {
+    points: Vec<Vec2>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    epsilon: f32,
+}
+
+impl Vertices {
+    fn new(epsilon: f32) -> Self {
+        Self { points: Vec::new(), cells: HashMap::new(), epsilon }
+    }
+    fn id(&mut self, point: Vec2) -> usize {
+        let cell = |v: f32| (v / self.epsilon).floor() as i64;
+        let (x, y) = (cell(point.x), cell(point.y));
+        for cx in x - 1..=x + 1 {
+            for cy in y - 1..=y + 1 {
+                let found = self.cells.get(&(cx, cy))
+                    .and_then(|ids| ids.iter().find(|&&id| self.points[id].distance(point) <= self.epsilon));
+                if let Some(&id) = found {
+                    return id;
+                }
+            }
+        }
+        self.points.push(point);
+        self.cells.entry((x, y)).or_default().push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+/// Winding numbers of both polygons just right of the middle of `segments[index]`.
+///
+/// A ray is cast from the middle towards the right, every other segment it crosses adds its counts;
+/// segments only meet at their ends, so the ray starts clear of all of them.
+fn winding_beside(segments: &[Segment], index: usize) -> [i32; 2] {
+    let segment = &segments[index];
+    let middle = (segment.from + segment.to) / 2.0;
+    let along = (segment.to - segment.from).normalize();
+    let right = Vec2::new(along.y, -along.x);
+    // 以射线方向为 x 轴、线段方向为 y 轴的坐标
+    let local = |p: Vec2| Vec2::new((p - middle).dot(right), (p - middle).dot(along));
+    let mut wind = [0, 0];
+    for (i, other) in segments.iter().enumerate() {
+        if i == index {
+            continue;
+        }
+        let (a, b) = (local(other.from), local(other.to));
+        if (a.y <= 0.0) == (b.y <= 0.0) {
+            continue;
+        }
+        let x = a.x + (0.0 - a.y) * (b.x - a.x) / (b.y - a.y);
+        if x > 0.0 {
+            let sign = if b.y > a.y { 1 } else { -1 };
+            wind[0] += sign * other.count[0];
+            wind[1] += sign * other.count[1];
+        }
+    }
+    wind
+}
+
+/// Joins directed segments into closed contours; where several leave the same vertex the sharpest
+/// left turn is taken, which keeps shapes touching at a corner apart.
+fn link(boundary: Vec<(Vec2, Vec2)>, epsilon: f32) -> Vec<Vec<(f32, f32)>> {
+    let mut vertices = Vertices::new(epsilon);
+    let edges: Vec<(usize, usize)> = boundary.into_iter().map(|(from, to)| (vertices.id(from), vertices.id(to))).collect();
+    let mut leaving: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(from, _)) in edges.iter().enumerate() {
+        leaving.entry(from).or_default().push(i);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut contours = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut contour = Vec::new();
+        let mut edge = start;
+        loop {
+            used[edge] = true;
+            let (from, to) = edges[edge];
+            contour.push(vertices.points[from]);
+            let incoming = vertices.points[to] - vertices.points[from];
+            let next = leaving.get(&to).into_iter().flatten().copied()
+                .filter(|&next| !used[next])
+                .max_by(|&a, &b| {
+                    let turn = |next: usize| {
+                        let outgoing = vertices.points[edges[next].1] - vertices.points[to];
+                        incoming.perp_dot(outgoing).atan2(incoming.dot(outgoing))
+                    };
+                    turn(a).total_cmp(&turn(b))
+                });
+            match next {
+                Some(next) => edge = next,
+                None => break,
+            }
+        }
+        let contour = without_straight_points(contour);
+        if contour.len() >= 3 && signed_area(contour.iter().copied()).abs() > epsilon * epsilon {
+            contours.push(contour.into_iter().map(Into::into).collect());
+        }
+    }
+    contours
+}
+
+/// Drops points in the middle of straight runs, left behind where edges were cut.
+fn without_straight_points(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    let mut i = 0;
+    while i < points.len() && points.len() >= 3 {
+        let n = points.len();
+        let (a, b, c) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+        let (ab, bc) = (b - a, c - b);
+        if ab.perp_dot(bc).abs() <= 1e-6 * ab.length() * bc.length() && ab.dot(bc) > 0.0 {
+            points.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fill::tessellate;
+
+    fn square(min: (f32, f32), size: f32) -> Vec<(f32, f32)> {
+        let (x, y) = min;
+        vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)]
+    }
+
+    fn area(contours: &[Vec<(f32, f32)>]) -> f32 {
+        tessellate(contours.iter().map(Vec::as_slice), FillRule::EvenOdd).area()
+    }
+
+    fn combine(a: &[Vec<(f32, f32)>], b: &[Vec<(f32, f32)>], operation: Operation) -> Vec<Vec<(f32, f32)>> {
+        boolean(a, FillRule::NonZero, b, FillRule::NonZero, operation)
+    }
+
+    #[test]
+    fn overlapping_squares() {
+        let (a, b) = ([square((0.0, 0.0), 2.0)], [square((1.0, 1.0), 2.0)]);
+        let expected = [(Operation::Union, 7.0), (Operation::Intersection, 1.0), (Operation::Difference, 3.0), (Operation::Xor, 6.0)];
+        for (operation, expected) in expected {
+            let result = combine(&a, &b, operation);
+            assert!((area(&result) - expected).abs() < 1e-4, "{operation:?}: {result:?}");
+        }
+        assert_eq!(combine(&a, &b, Operation::Union)[0].len(), 8);
+        // 异或是两块只在角上相接的区域
+        assert_eq!(split_outlines(combine(&a, &b, Operation::Xor)).len(), 2);
+    }
+
+    #[test]
+    fn coincident_edges() {
+        let a = [square((0.0, 0.0), 1.0)];
+        let union = combine(&a, &[square((1.0, 0.0), 1.0)], Operation::Union);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].len(), 4, "{union:?}");
+        assert!((area(&union) - 2.0).abs() < 1e-5);
+
+        // 只有一半重合的边
+        let shifted = combine(&a, &[square((1.0, 0.5), 1.0)], Operation::Union);
+        assert_eq!((shifted.len(), shifted[0].len()), (1, 8));
+
+        // 同样的两个正方形
+        assert!((area(&combine(&a, &a, Operation::Union)) - 1.0).abs() < 1e-5);
+        assert!(combine(&a, &a, Operation::Xor).is_empty());
+        assert!(combine(&a, &a, Operation::Difference).is_empty());
+        let reversed: Vec<(f32, f32)> = a[0].iter().rev().copied().collect();
+        assert!((area(&combine(&a, &[reversed], Operation::Intersection)) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn holes() {
+        let frame = [square((0.0, 0.0), 3.0), square((1.0, 1.0), 1.0)];
+        let plug = [square((1.0, 1.0), 1.0)];
+        let filled = boolean(&frame, FillRule::EvenOdd, &plug, FillRule::NonZero, Operation::Union);
+        assert_eq!(filled.len(), 1);
+        assert!((area(&filled) - 9.0).abs() < 1e-4);
+        assert!(boolean(&frame, FillRule::EvenOdd, &plug, FillRule::NonZero, Operation::Intersection).is_empty());
+
+        // 穿过洞的一条横杠
+        let bar = [vec![(-1.0, 1.25), (4.0, 1.25), (4.0, 1.75), (-1.0, 1.75)]];
+        let cut = boolean(&frame, FillRule::EvenOdd, &bar, FillRule::NonZero, Operation::Difference);
+        assert!((area(&cut) - (8.0 - 1.5 + 0.5)).abs() < 1e-4);
+        assert_eq!(split_outlines(cut).len(), 2);
+
+        let framed = split_outlines(simplify(&frame, FillRule::EvenOdd));
+        assert_eq!((framed.len(), framed[0].len()), (1, 2));
+
+        // 自相交的八字形在非零规则下是两块
+        let bow = [vec![(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]];
+        let simplified = simplify(&bow, FillRule::NonZero);
+        assert_eq!(simplified.len(), 2);
+        assert!((area(&simplified) - 2.0).abs() < 1e-4);
+    }
+}
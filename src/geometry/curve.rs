@@ -0,0 +1,192 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::{Mat2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// How a path gets from one point to the next.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Segment {
+    #[default]
+    Line,
+    Quadratic((f32, f32)),
+    Cubic((f32, f32), (f32, f32)),
+    /// Elliptical arc with SVG endpoint parameters, `rotation` being in degrees.
+    Arc { radii: (f32, f32), rotation: f32, large_arc: bool, sweep: bool },
+}
+
+impl Segment {
+    /// Appends points along the segment from `from` to `to`, excluding `from`.
+    pub fn flatten(self, from: Vec2, to: Vec2, tolerance: f32, out: &mut Vec<Vec2>) {
+        match self {
+            Segment::Line => out.push(to),
+            Segment::Quadratic(c) => quadratic(from, c.into(), to, tolerance, out),
+            Segment::Cubic(c1, c2) => cubic(from, c1.into(), c2.into(), to, tolerance, out),
+            Segment::Arc { radii, rotation, large_arc, sweep } => {
+                match Arc::from_endpoints(from, radii.into(), rotation, large_arc, sweep, to) {
+                    Some(arc) => arc.flatten(tolerance, out),
+                    None => out.push(to),
+                }
+            }
+        }
+    }
+}
+
+/// Appends points along a quadratic Bézier curve, excluding `p0`, so that no point of the curve is
+/// further than `tolerance` from the resulting polyline.
+pub fn quadratic(p0: Vec2, c: Vec2, p1: Vec2, tolerance: f32, out: &mut Vec<Vec2>) {
+    let deviation = (p0 - 2.0 * c + p1).length() / 4.0;
+    let steps = steps((deviation / tolerance).sqrt());
+    out.extend((1..=steps).map(|i| {
+        let t = i as f32 / steps as f32;
+        let u = 1.0 - t;
+        p0 * u * u + c * 2.0 * u * t + p1 * t * t
+    }));
+}
+
+/// Appends points along a cubic Bézier curve, excluding `p0`.
+pub fn cubic(p0: Vec2, c1: Vec2, c2: Vec2, p1: Vec2, tolerance: f32, out: &mut Vec<Vec2>) {
+    let deviation = (p0 - 2.0 * c1 + c2).length().max((c1 - 2.0 * c2 + p1).length()) * 3.0 / 4.0;
+    let steps = steps((deviation / tolerance).sqrt());
+    out.extend((1..=steps).map(|i| {
+        let t = i as f32 / steps as f32;
+        let u = 1.0 - t;
+        p0 * u * u * u + c1 * 3.0 * u * u * t + c2 * 3.0 * u * t * t + p1 * t * t * t
+    }));
+}
+
+/// An elliptical arc in center parameterization.
+#[derive(Copy, Clone, Debug)]
+pub struct Arc {
+    pub center: Vec2,
+    pub radii: Vec2,
+    pub rotation: Mat2,
+    pub start_angle: f32,
+    pub sweep_angle: f32,
+}
+
+impl Arc {
+    /// Converts SVG style endpoint parameters, `rotation` being in degrees.
+    ///
+    /// Radii that are too small to reach `to` are scaled up. Returns `None` when the arc degenerates
+    /// into a straight line.
+    pub fn from_endpoints(from: Vec2, radii: Vec2, rotation: f32, large_arc: bool, sweep: bool, to: Vec2) -> Option<Arc> {
+        let mut radii = radii.abs();
+        if radii.x == 0.0 || radii.y == 0.0 || from == to {
+            return None;
+        }
+        // 按 SVG 规范附录 F.6 把端点参数化转换为中心参数化
+        let rotation = Mat2::from_angle(rotation.to_radians());
+        let p = rotation.transpose() * ((from - to) / 2.0);
+        let lambda = (p / radii).length_squared();
+        if lambda > 1.0 {
+            radii *= lambda.sqrt();
+        }
+        let (rx2, ry2) = (radii.x * radii.x, radii.y * radii.y);
+        let numerator = (rx2 * ry2 - rx2 * p.y * p.y - ry2 * p.x * p.x).max(0.0);
+        let mut coefficient = (numerator / (rx2 * p.y * p.y + ry2 * p.x * p.x)).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+        let center_p = Vec2::new(radii.x * p.y / radii.y, -radii.y * p.x / radii.x) * coefficient;
+        let center = rotation * center_p + (from + to) / 2.0;
+
+        let start = (p - center_p) / radii;
+        let end = (-p - center_p) / radii;
+        let start_angle = start.y.atan2(start.x);
+        let mut sweep_angle = end.y.atan2(end.x) - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += TAU;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= TAU;
+        }
+        Some(Arc { center, radii, rotation, start_angle, sweep_angle })
+    }
+    pub fn point_at(&self, angle: f32) -> Vec2 {
+        self.center + self.rotation * (Vec2::new(angle.cos(), angle.sin()) * self.radii)
+    }
+    fn derivative_at(&self, angle: f32) -> Vec2 {
+        self.rotation * (Vec2::new(-angle.sin(), angle.cos()) * self.radii)
+    }
+    /// Appends points along the arc, excluding its start.
+    pub fn flatten(&self, tolerance: f32, out: &mut Vec<Vec2>) {
+        let radius = self.radii.max_element();
+        let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+        let steps = steps(self.sweep_angle.abs() / step.max(1e-3));
+        out.extend((1..=steps).map(|i| self.point_at(self.start_angle + self.sweep_angle * i as f32 / steps as f32)));
+    }
+    /// Approximates the arc with cubic Béziers of at most a quarter turn each, as `(c1, c2, end)`.
+    pub fn to_cubics(self) -> Vec<(Vec2, Vec2, Vec2)> {
+        let pieces = (self.sweep_angle.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+        let delta = self.sweep_angle / pieces as f32;
+        let k = 4.0 / 3.0 * (delta / 4.0).tan();
+        (0..pieces).map(|i| {
+            let a0 = self.start_angle + delta * i as f32;
+            let a1 = a0 + delta;
+            (
+                self.point_at(a0) + self.derivative_at(a0) * k,
+                self.point_at(a1) - self.derivative_at(a1) * k,
+                self.point_at(a1),
+            )
+        }).collect()
+    }
+}
+
+fn steps(estimate: f32) -> usize {
+    if estimate.is_finite() {
+        (estimate.ceil() as usize).clamp(1, 1024)
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_bounds_the_error() {
+        let (p0, c, p1) = (Vec2::ZERO, Vec2::new(1.0, 2.0), Vec2::new(2.0, 0.0));
+        for tolerance in [0.1, 0.01, 0.001] {
+            let mut points = vec![p0];
+            quadratic(p0, c, p1, tolerance, &mut points);
+            // 抛物线的顶点在 t = 0.5 处，落在两段之间时误差最大
+            let apex = Vec2::new(1.0, 1.0);
+            let error = points.windows(2)
+                .map(|s| {
+                    let t = ((apex - s[0]).dot(s[1] - s[0]) / (s[1] - s[0]).length_squared()).clamp(0.0, 1.0);
+                    apex.distance(s[0] + (s[1] - s[0]) * t)
+                })
+                .fold(f32::INFINITY, f32::min);
+            assert!(error <= tolerance, "error {error} exceeds {tolerance}");
+        }
+        let (mut coarse, mut fine) = (Vec::new(), Vec::new());
+        cubic(p0, c, c, p1, 0.1, &mut coarse);
+        cubic(p0, c, c, p1, 0.001, &mut fine);
+        assert!(fine.len() > coarse.len());
+        assert_eq!(*fine.last().unwrap(), p1);
+    }
+
+    #[test]
+    fn arc_from_endpoints() {
+        let arc = Arc::from_endpoints(Vec2::new(-1.0, 0.0), Vec2::ONE, 0.0, false, true, Vec2::new(1.0, 0.0)).unwrap();
+        assert!(arc.center.length() < 1e-5);
+        assert!((arc.sweep_angle.abs() - std::f32::consts::PI).abs() < 1e-5);
+
+        let mut points = Vec::new();
+        arc.flatten(0.001, &mut points);
+        assert!(points.iter().all(|p| (p.length() - 1.0).abs() < 1e-4));
+        assert!((*points.last().unwrap() - Vec2::new(1.0, 0.0)).length() < 1e-5);
+
+        // 半径不够时按比例放大
+        let small = Arc::from_endpoints(Vec2::ZERO, Vec2::splat(0.1), 0.0, false, false, Vec2::new(4.0, 0.0)).unwrap();
+        assert!((small.radii.x - 2.0).abs() < 1e-4);
+
+        let mut start = arc.point_at(arc.start_angle);
+        for (c1, c2, end) in arc.to_cubics() {
+            let mut points = vec![];
+            cubic(start, c1, c2, end, 0.01, &mut points);
+            assert!(points.iter().all(|p| (p.length() - 1.0).abs() < 1e-3));
+            start = end;
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::triangulate::{segments_intersect, triangulate};
+use super::Mesh;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    pub fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+    pub fn toggled(self) -> Self {
+        match self {
+            FillRule::NonZero => FillRule::EvenOdd,
+            FillRule::EvenOdd => FillRule::NonZero,
+        }
+    }
+}
+
+struct Edge {
+    top: Vec2,
+    bottom: Vec2,
+    winding: i32,
+    operand: usize,
+}
+
+impl Edge {
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top.y) / (self.bottom.y - self.top.y);
+        self.top.x + (self.bottom.x - self.top.x) * t
+    }
+}
+
+/// Triangulates the area covered by a set of closed contours under the given fill rule.
+///
+/// A single simple contour is ear clipped. Anything else — holes, overlapping contours or
+/// self-intersections — is cut into horizontal bands at every vertex and crossing, and each band is
+/// covered by the trapezoids between edges whose accumulated winding is inside.
+pub fn tessellate<'a>(contours: impl IntoIterator<Item = &'a [(f32, f32)]>, rule: FillRule) -> Mesh {
+    let contours: Vec<&[(f32, f32)]> = contours.into_iter().filter(|c| c.len() >= 3).collect();
+    if let [contour] = contours[..] {
+        if let Ok(triangles) = triangulate(contour) {
+            return Mesh {
+                vertices: contour.iter().map(|&p| p.into()).collect(),
+                triangles: triangles.into_iter().map(|t| t.map(|i| i as u32)).collect(),
+            };
+        }
+    }
+
+    let mut edges = Vec::new();
+    push_edges(contours, 0, &mut edges);
+    sweep(edges, &[rule]).pop().map_or_else(Mesh::default, |(_, mesh)| mesh)
+}
+
+/// Triangulates several sets of contours together, each under its own fill rule, and groups the
+/// area by the sets covering it: every point that any set covers lies in exactly one of the
+/// returned meshes, which comes with the ascending indices of those sets.
+pub fn overlay<'a>(operands: impl IntoIterator<Item = (&'a [Vec<(f32, f32)>], FillRule)>) -> Vec<(Vec<usize>, Mesh)> {
+    let mut edges = Vec::new();
+    let mut rules = Vec::new();
+    for (operand, (contours, rule)) in operands.into_iter().enumerate() {
+        push_edges(contours.iter().map(Vec::as_slice), operand, &mut edges);
+        rules.push(rule);
+    }
+    sweep(edges, &rules)
+}
+
+fn push_edges<'a>(contours: impl IntoIterator<Item = &'a [(f32, f32)]>, operand: usize, edges: &mut Vec<Edge>) {
+    for contour in contours {
+        for i in 0..contour.len() {
+            let (a, b) = (Vec2::from(contour[i]), Vec2::from(contour[(i + 1) % contour.len()]));
+            if a.y == b.y {
+                continue;
+            }
+            edges.push(if a.y < b.y {
+                Edge { top: a, bottom: b, winding: 1, operand }
+            } else {
+                Edge { top: b, bottom: a, winding: -1, operand }
+            });
+        }
+    }
+}
+
+/// Cuts the plane into bands at every vertex and crossing and covers each band with the trapezoids
+/// between edges, sorted by the operands whose winding, under `rules`, is inside them.
+fn sweep(mut edges: Vec<Edge>, rules: &[FillRule]) -> Vec<(Vec<usize>, Mesh)> {
+    // 按上端排序后，每条边只需和上端落在它范围内的边求交，扫描时也能逐带增删活动边
+    edges.sort_by(|a, b| a.top.y.total_cmp(&b.top.y));
+    let mut ys: Vec<f32> = edges.iter().flat_map(|e| [e.top.y, e.bottom.y]).collect();
+    for (i, a) in edges.iter().enumerate() {
+        for b in edges[i + 1..].iter().take_while(|b| b.top.y < a.bottom.y) {
+            ys.extend(crossing(a, b));
+        }
+    }
+    ys.sort_by(f32::total_cmp);
+    ys.dedup();
+
+    let mut regions: Vec<(Vec<usize>, Mesh)> = Vec::new();
+    let mut lookup: HashMap<Vec<usize>, usize> = HashMap::new();
+    let mut pending = edges.iter().peekable();
+    let mut active: Vec<&Edge> = Vec::new();
+    let mut row: Vec<(f32, &Edge)> = Vec::new();
+    let mut spans: Vec<(usize, &Edge, &Edge)> = Vec::new();
+    let mut winding = vec![0; rules.len()];
+    let mut covering: Vec<usize> = Vec::new();
+    for band in ys.windows(2) {
+        let (y0, y1) = (band[0], band[1]);
+        let middle = (y0 + y1) / 2.0;
+        active.retain(|e| e.bottom.y > y0);
+        while let Some(edge) = pending.next_if(|e| e.top.y <= y0) {
+            active.push(edge);
+        }
+        row.clear();
+        row.extend(active.iter().map(|&e| (e.x_at(middle), e)));
+        row.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut left: Option<&Edge> = None;
+        spans.clear();
+        for &(_, edge) in &row {
+            let operand = edge.operand;
+            let was_inside = rules[operand].is_inside(winding[operand]);
+            winding[operand] += edge.winding;
+            if was_inside == rules[operand].is_inside(winding[operand]) {
+                continue;
+            }
+            if let Some(left) = left.take() {
+                let region = match lookup.get(covering.as_slice()) {
+                    Some(&region) => region,
+                    None => {
+                        lookup.insert(covering.clone(), regions.len());
+                        regions.push((covering.clone(), Mesh::default()));
+                        regions.len() - 1
+                    }
+                };
+                // 同一区域只隔着一条重合的边时并成一个梯形
+                match spans.last_mut() {
+                    Some(last) if last.0 == region && coincide(last.2, left, y0, y1) => last.2 = edge,
+                    _ => spans.push((region, left, edge)),
+                }
+            }
+            match covering.binary_search(&operand) {
+                Ok(i) => { covering.remove(i); }
+                Err(i) => covering.insert(i, operand),
+            }
+            if !covering.is_empty() {
+                left = Some(edge);
+            }
+        }
+        for &(region, left, right) in &spans {
+            regions[region].1.push_quad([
+                Vec2::new(left.x_at(y0), y0),
+                Vec2::new(right.x_at(y0), y0),
+                Vec2::new(right.x_at(y1), y1),
+                Vec2::new(left.x_at(y1), y1),
+            ]);
+        }
+    }
+    regions
+}
+
+fn coincide(a: &Edge, b: &Edge, y0: f32, y1: f32) -> bool {
+    [y0, y1].iter().all(|&y| {
+        let (xa, xb) = (a.x_at(y), b.x_at(y));
+        (xa - xb).abs() <= 1e-5 * xa.abs().max(1.0)
+    })
+}
+
+/// Sum of the windings of the contours around `point`, counting counter-clockwise turns as positive.
+pub fn winding<'a>(contours: impl IntoIterator<Item = &'a [(f32, f32)]>, point: Vec2) -> i32 {
+    let mut winding = 0;
+    for contour in contours {
+        for i in 0..contour.len() {
+            let (a, b) = (Vec2::from(contour[i]), Vec2::from(contour[(i + 1) % contour.len()]));
+            // 向右发射一条水平射线，统计穿过它的有向边
+            if a.y <= point.y && b.y > point.y && (b - a).perp_dot(point - a) > 0.0 {
+                winding += 1;
+            } else if b.y <= point.y && a.y > point.y && (b - a).perp_dot(point - a) < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
+/// The y coordinate where two edges properly cross, if they do.
+fn crossing(a: &Edge, b: &Edge) -> Option<f32> {
+    if a.bottom.y <= b.top.y || b.bottom.y <= a.top.y || !segments_intersect(a.top, a.bottom, b.top, b.bottom) {
+        return None;
+    }
+    let (da, db) = (a.bottom - a.top, b.bottom - b.top);
+    let denominator = da.perp_dot(db);
+    if denominator == 0.0 {
+        return None;
+    }
+    let t = (b.top - a.top).perp_dot(db) / denominator;
+    let y = a.top.y + da.y * t;
+    (y > a.top.y.max(b.top.y) && y < a.bottom.y.min(b.bottom.y)).then_some(y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(min: f32, max: f32) -> Vec<(f32, f32)> {
+        vec![(min, min), (max, min), (max, max), (min, max)]
+    }
+
+    fn area(contours: &[Vec<(f32, f32)>], rule: FillRule) -> f32 {
+        tessellate(contours.iter().map(Vec::as_slice), rule).area()
+    }
+
+    #[test]
+    fn hole_with_opposite_winding() {
+        let mut hole = square(1.0, 2.0);
+        hole.reverse();
+        let contours = [square(0.0, 3.0), hole];
+        assert!((area(&contours, FillRule::NonZero) - 8.0).abs() < 1e-4);
+        assert!((area(&contours, FillRule::EvenOdd) - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hole_with_same_winding() {
+        let contours = [square(0.0, 3.0), square(1.0, 2.0)];
+        assert!((area(&contours, FillRule::NonZero) - 9.0).abs() < 1e-4);
+        assert!((area(&contours, FillRule::EvenOdd) - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn nested_contours() {
+        let contours = [square(0.0, 5.0), square(1.0, 4.0), square(2.0, 3.0)];
+        assert!((area(&contours, FillRule::EvenOdd) - (25.0 - 9.0 + 1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn overlapping_contours() {
+        let contours = [square(0.0, 2.0), square(1.0, 3.0)];
+        assert!((area(&contours, FillRule::NonZero) - 7.0).abs() < 1e-4);
+        assert!((area(&contours, FillRule::EvenOdd) - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn winding_around_holes() {
+        let mut hole = square(1.0, 2.0);
+        hole.reverse();
+        let contours = [square(0.0, 3.0), hole];
+        let at = |x, y| winding(contours.iter().map(Vec::as_slice), Vec2::new(x, y));
+        assert_eq!(at(0.5, 0.5), 1);
+        assert_eq!(at(1.5, 1.5), 0);
+        assert_eq!(at(4.0, 1.5), 0);
+        assert_eq!(winding([square(0.0, 3.0).as_slice(), square(1.0, 2.0).as_slice()], Vec2::splat(1.5)), 2);
+    }
+
+    #[test]
+    fn overlay_splits_shared_area() {
+        let (a, b) = ([square(0.0, 2.0)], [square(1.0, 3.0)]);
+        let mut regions = overlay([(&a[..], FillRule::NonZero), (&b[..], FillRule::NonZero)]);
+        regions.sort_by_key(|(covering, _)| covering.clone());
+        let areas: Vec<(Vec<usize>, f32)> = regions.into_iter().map(|(covering, mesh)| (covering, mesh.area())).collect();
+        assert_eq!(areas.iter().map(|(covering, _)| covering.clone()).collect::<Vec<_>>(), [vec![0], vec![0, 1], vec![1]]);
+        for ((_, area), expected) in areas.iter().zip([3.0, 1.0, 3.0]) {
+            assert!((area - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn self_intersecting_contour() {
+        let bowtie = vec![(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)];
+        assert!((area(&[bowtie], FillRule::NonZero) - 2.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,37 @@
+use glam::Vec2;
+
+pub mod boolean;
+pub mod curve;
+pub mod fill;
+pub mod offset;
+pub mod stroke;
+pub mod triangulate;
+
+/// Triangles produced by the tessellators, indexing into `vertices`.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vec2>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    pub fn push_quad(&mut self, corners: [Vec2; 4]) {
+        let origin = self.vertices.len() as u32;
+        self.vertices.extend(corners);
+        self.triangles.push([origin, origin + 1, origin + 2]);
+        self.triangles.push([origin, origin + 2, origin + 3]);
+    }
+    pub fn append(&mut self, other: Mesh) {
+        let origin = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.triangles.extend(other.triangles.into_iter().map(|t| t.map(|i| origin + i)));
+    }
+    pub fn area(&self) -> f32 {
+        self.triangles.iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| self.vertices[i as usize]);
+                (b - a).perp_dot(c - a).abs() / 2.0
+            })
+            .sum()
+    }
+}
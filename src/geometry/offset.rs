@@ -0,0 +1,162 @@
+//! Growing and shrinking filled areas by a distance, and outlining open polylines.
+//!
+//! Every edge is moved sideways by the distance. Where the moved edges of a corner part, the gap is
+//! closed with a miter, an arc or a square end; where they overlap, they are joined through the
+//! corner point itself. The loops this leaves wherever the result would overlap itself are resolved
+//! with `boolean::clip`, keeping what the moved contours wind around counter-clockwise.
+
+use glam::Vec2;
+
+use super::boolean::{clip, simplify};
+use super::fill::FillRule;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    /// Sharp corners, squared off beyond the miter limit.
+    #[default]
+    Miter,
+    Round,
+    /// Corners cut off square at the offset distance.
+    Square,
+}
+
+/// Grows the area `contours` cover under `rule` by `distance`, or shrinks it for negative distances.
+///
+/// Miters reaching further than `miter_limit` times the distance are squared off, and round corners
+/// stay within `tolerance` of the arc. The result is laid out like the result of `boolean::boolean`.
+pub fn offset(contours: &[Vec<(f32, f32)>], rule: FillRule, distance: f32, corner: Corner, miter_limit: f32,
+              tolerance: f32) -> Vec<Vec<(f32, f32)>> {
+    // 先化成不自交的轮廓：外轮廓逆时针、洞顺时针，内部总在左边
+    let simple = simplify(contours, rule);
+    if distance == 0.0 {
+        return simple;
+    }
+    let moved: Vec<Vec<(f32, f32)>> = simple.iter()
+        .map(|contour| shift(contour, distance, corner, miter_limit, tolerance))
+        .collect();
+    clip([&moved, &[]], |wind| wind[0] > 0)
+}
+
+/// The closed outline around the open polyline `points`, reaching `distance` to either side; the
+/// ends are shaped like the corners.
+pub fn outline(points: &[(f32, f32)], distance: f32, corner: Corner, miter_limit: f32,
+               tolerance: f32) -> Vec<Vec<(f32, f32)>> {
+    let mut points = points.to_vec();
+    points.dedup();
+    if points.len() < 2 || distance == 0.0 {
+        return Vec::new();
+    }
+    // 沿折线走过去再走回来，两头掉头的地方成为线帽
+    let back: Vec<(f32, f32)> = points[1..points.len() - 1].iter().rev().copied().collect();
+    points.extend(back);
+    clip([&[shift(&points, distance.abs(), corner, miter_limit, tolerance)], &[]], |wind| wind[0] > 0)
+}
+
+/// Moves the closed contour `points` by `distance` to the right of its direction.
+fn shift(points: &[(f32, f32)], distance: f32, corner: Corner, miter_limit: f32, tolerance: f32) -> Vec<(f32, f32)> {
+    let mut points: Vec<Vec2> = points.iter().map(|&p| p.into()).collect();
+    points.dedup();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let n = points.len();
+    let reach = distance.abs();
+    let mut moved = Vec::new();
+    for i in 0..n {
+        let (prev, p, next) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+        let (d0, d1) = ((p - prev).normalize(), (next - p).normalize());
+        let (o0, o1) = (-d0.perp() * distance, -d1.perp() * distance);
+        let turn = d0.perp_dot(d1);
+        let reverses = turn.abs() <= 1e-6 && d0.dot(d1) < 0.0;
+        if turn.abs() <= 1e-6 && !reverses {
+            moved.push(p + o0);
+            continue;
+        }
+        // 向左转时右边是外侧；内侧经过拐点本身，多出的小环交给 clip 去掉
+        if !reverses && turn * distance < 0.0 {
+            moved.extend([p + o0, p, p + o1]);
+            continue;
+        }
+        let cos_half = ((1.0 + d0.dot(d1)) / 2.0).max(0.0).sqrt();
+        let corner = match corner {
+            Corner::Miter if cos_half <= 1e-6 || 1.0 / cos_half > miter_limit => Corner::Square,
+            corner => corner,
+        };
+        match corner {
+            Corner::Miter => moved.push(p + (o0 + o1).normalize() * (reach / cos_half)),
+            Corner::Round => {
+                // 弦高不超过误差，掉头时绕过前方
+                let sweep = (2.0 * cos_half.acos()).copysign(distance);
+                let step = 2.0 * (1.0 - tolerance / reach).max(-1.0).acos();
+                let count = (sweep.abs() / step.max(1e-3)).ceil().max(1.0) as usize;
+                moved.extend((0..=count).map(|k| p + Vec2::from_angle(sweep * k as f32 / count as f32).rotate(o0)));
+            }
+            Corner::Square => {
+                let outward = if reverses { d0 } else { (o0 + o1).normalize() };
+                // 两条移动后的边各自延长到离拐点正好 reach 的切线上
+                let t0 = (reach - o0.dot(outward)) / d0.dot(outward);
+                let t1 = (reach - o1.dot(outward)) / -d1.dot(outward);
+                moved.extend([p + o0 + d0 * t0, p + o1 - d1 * t1]);
+            }
+        }
+    }
+    moved.into_iter().map(Into::into).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::geometry::fill::tessellate;
+
+    fn area(contours: &[Vec<(f32, f32)>]) -> f32 {
+        tessellate(contours.iter().map(Vec::as_slice), FillRule::NonZero).area()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn corners_of_a_grown_square() {
+        let square = [vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]];
+        let grown = |corner| area(&offset(&square, FillRule::NonZero, 0.5, corner, 4.0, 1e-4));
+        assert!(close(grown(Corner::Miter), 9.0));
+        assert!(close(grown(Corner::Round), 4.0 + 4.0 + 0.25 * PI));
+        // 直角的方头离拐点 0.5，切掉四个角
+        let cut = 4.0 * (0.5 * 2f32.sqrt() - 0.5).powi(2);
+        assert!(close(grown(Corner::Square), 9.0 - cut), "{}", grown(Corner::Square));
+        // 超过斜接限制时变成方头
+        assert!(close(area(&offset(&square, FillRule::NonZero, 0.5, Corner::Miter, 1.2, 1e-4)), 9.0 - cut));
+
+        let shrunk = offset(&square, FillRule::NonZero, -0.5, Corner::Round, 4.0, 1e-4);
+        assert!(close(area(&shrunk), 1.0));
+        assert!(offset(&square, FillRule::NonZero, -1.5, Corner::Miter, 4.0, 1e-4).is_empty());
+    }
+
+    #[test]
+    fn loops_and_holes_are_resolved() {
+        // 凹口比偏移距离窄，放大后被填上
+        let notched = [vec![(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (2.2, 2.0), (2.2, 1.0), (1.8, 1.0), (1.8, 2.0), (0.0, 2.0)]];
+        let grown = offset(&notched, FillRule::NonZero, 0.5, Corner::Miter, 4.0, 1e-4);
+        assert_eq!(grown.len(), 1);
+        assert!(close(area(&grown), 5.0 * 3.0), "{grown:?}");
+
+        // 缩小时洞变大，再缩就断开了
+        let frame = [vec![(0.0, 0.0), (5.0, 0.0), (5.0, 3.0), (0.0, 3.0)], vec![(1.0, 1.0), (4.0, 1.0), (4.0, 2.0), (1.0, 2.0)]];
+        let thinner = offset(&frame, FillRule::EvenOdd, -0.25, Corner::Miter, 4.0, 1e-4);
+        assert!(close(area(&thinner), 4.5 * 2.5 - 3.5 * 1.5));
+        assert_eq!(thinner.len(), 2);
+        assert!(offset(&frame, FillRule::EvenOdd, -0.6, Corner::Miter, 4.0, 1e-4).is_empty());
+    }
+
+    #[test]
+    fn open_lines_get_outlined() {
+        let line = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)];
+        assert!(close(area(&outline(&line, 0.5, Corner::Miter, 4.0, 1e-4)), 5.0));
+        let round = area(&outline(&line, -0.5, Corner::Round, 4.0, 1e-4));
+        assert!(close(round, 3.75 + 0.3125 * PI), "{round}");
+        assert!(outline(&[(1.0, 1.0)], 0.5, Corner::Round, 4.0, 1e-4).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use std::f32::consts::PI;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::fill::{tessellate, FillRule};
+use super::Mesh;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Join {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl Join {
+    pub fn next(self) -> Self {
+        match self {
+            Join::Miter => Join::Round,
+            Join::Round => Join::Bevel,
+            Join::Bevel => Join::Miter,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cap {
+    #[default]
+    Butt,
+    Square,
+    Round,
+}
+
+impl Cap {
+    pub fn next(self) -> Self {
+        match self {
+            Cap::Butt => Cap::Square,
+            Cap::Square => Cap::Round,
+            Cap::Round => Cap::Butt,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub join: Join,
+    pub cap: Cap,
+    /// Longest allowed miter as a multiple of the width, longer miters fall back to bevels.
+    pub miter_limit: f32,
+}
+
+/// Angle covered by one triangle of a round join or cap.
+const ROUND_STEP: f32 = PI / 12.0;
+
+/// Outlines a polyline as triangles, with joins between segments and caps at the ends of open lines.
+///
+/// Consecutive duplicate points are ignored; a line that collapses into a single point only shows
+/// its caps, which is nothing for butt caps. Every point of the stroke is covered by one triangle,
+/// so translucent strokes blend evenly.
+pub fn stroke(points: &[(f32, f32)], closed: bool, style: &Stroke, mesh: &mut Mesh) {
+    let mut contours = Vec::new();
+    outline(points, closed, style, &mut contours);
+    mesh.append(tessellate(contours.iter().map(Vec::as_slice), FillRule::NonZero));
+}
+
+/// Adds the contours around the stroke of a polyline: the right side forward and the left side
+/// back, joined by the caps of an open line. The contours wind once or more around every point
+/// of the stroke and loop over themselves where it overlaps, so they are meant to be filled with
+/// [`FillRule::NonZero`], together with the contours of other lines that should blend as one.
+pub fn outline(points: &[(f32, f32)], closed: bool, style: &Stroke, contours: &mut Vec<Vec<(f32, f32)>>) {
+    let half = style.width / 2.0;
+    if half <= 0.0 {
+        return;
+    }
+    let mut points: Vec<Vec2> = points.iter().map(|&p| p.into()).collect();
+    points.dedup_by(|a, b| a.distance_squared(*b) <= f32::EPSILON * f32::EPSILON);
+    if closed && points.len() > 1 && points[0].distance_squared(*points.last().unwrap()) <= f32::EPSILON * f32::EPSILON {
+        points.pop();
+    }
+
+    let contour = match points[..] {
+        [] => return,
+        [p] => match style.cap {
+            Cap::Butt => return,
+            Cap::Square => vec![
+                p + Vec2::new(-half, -half),
+                p + Vec2::new(half, -half),
+                p + Vec2::new(half, half),
+                p + Vec2::new(-half, half),
+            ],
+            Cap::Round => arc(p, Vec2::X * half, 2.0 * PI),
+        },
+        _ if closed && points.len() > 2 => {
+            let right = side(&points, true, true, half, style);
+            points.reverse();
+            contours.push(right.into_iter().map(Into::into).collect());
+            side(&points, true, false, half, style)
+        }
+        _ => {
+            let n = points.len();
+            let mut contour = side(&points, false, true, half, style);
+            contour.extend(cap(points[n - 1], points[n - 2], half, style.cap));
+            points.reverse();
+            contour.extend(side(&points, false, false, half, style));
+            contour.extend(cap(points[n - 1], points[n - 2], half, style.cap));
+            contour
+        }
+    };
+    contours.push(contour.into_iter().map(Into::into).collect());
+}
+
+/// Walks the right side of the line. `forward` tells the two passes over a line apart, so that a
+/// line folding straight back gets its join on one side only.
+fn side(points: &[Vec2], closed: bool, forward: bool, half: f32, style: &Stroke) -> Vec<Vec2> {
+    let n = points.len();
+    let right = |a: Vec2, b: Vec2| -(b - a).normalize().perp() * half;
+    let mut side = Vec::new();
+    if !closed {
+        side.push(points[0] + right(points[0], points[1]));
+    }
+    let joints = if closed { 0..n } else { 1..n - 1 };
+    for i in joints {
+        corner(points[(i + n - 1) % n], points[i], points[(i + 1) % n], forward, half, style, &mut side);
+    }
+    if !closed {
+        side.push(points[n - 1] + right(points[n - 2], points[n - 1]));
+    }
+    side
+}
+
+/// Adds the points of the right side around the corner at `p`: the join when the line turns left,
+/// otherwise the point where the offset edges meet.
+fn corner(prev: Vec2, p: Vec2, next: Vec2, forward: bool, half: f32, style: &Stroke, side: &mut Vec<Vec2>) {
+    let (d0, d1) = ((p - prev).normalize(), (next - p).normalize());
+    let (r0, r1) = (-d0.perp() * half, -d1.perp() * half);
+    let turn = d0.perp_dot(d1);
+    if turn.abs() <= 1e-6 && d0.dot(d1) > 0.0 {
+        side.push(p + r0);
+        return;
+    }
+    let bisector = (r0 + r1).normalize_or_zero();
+    let cos_half = bisector.dot(r0 / half);
+    if turn > 0.0 || (turn == 0.0 && forward) {
+        // 左转时右边是外侧
+        match style.join {
+            Join::Round => side.extend(arc(p, r0, r0.angle_between(r1))),
+            // 斜接长度与线宽之比等于 1 / cos(转角 / 2)
+            Join::Miter if cos_half > 1e-6 && 1.0 / cos_half <= style.miter_limit => {
+                side.extend([p + r0, p + bisector * (half / cos_half), p + r1]);
+            }
+            _ => side.extend([p + r0, p + r1]),
+        }
+        return;
+    }
+    // 内侧两条边的交点没有越过相邻线段时直接连过去，否则绕回中心点
+    let behind = half * (1.0 / (cos_half * cos_half) - 1.0).max(0.0).sqrt();
+    if cos_half > 1e-6 && behind <= p.distance(prev).min(p.distance(next)) {
+        side.push(p + bisector * (half / cos_half));
+    } else {
+        side.extend([p + r0, p, p + r1]);
+    }
+}
+
+/// Points of the cap around the end `p` of a line whose neighbouring point is `inner`, going from
+/// the right side to the left.
+fn cap(p: Vec2, inner: Vec2, half: f32, cap: Cap) -> Vec<Vec2> {
+    let outward = (p - inner).normalize() * half;
+    let normal = outward.perp();
+    match cap {
+        Cap::Butt => Vec::new(),
+        Cap::Square => vec![p - normal + outward, p + normal + outward],
+        Cap::Round => arc(p, -normal, PI),
+    }
+}
+
+/// Arc around `center` starting at offset `from` and sweeping `angle` counter-clockwise, ends included.
+fn arc(center: Vec2, from: Vec2, angle: f32) -> Vec<Vec2> {
+    let steps = (angle.abs() / ROUND_STEP).ceil().max(1.0) as u32;
+    (0..=steps).map(|i| center + Vec2::from_angle(angle * i as f32 / steps as f32).rotate(from)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(join: Join, cap: Cap) -> Stroke {
+        Stroke { width: 0.2, join, cap, miter_limit: 4.0 }
+    }
+
+    fn bounds(mesh: &Mesh) -> (Vec2, Vec2) {
+        let min = mesh.vertices.iter().fold(Vec2::splat(f32::INFINITY), |a, &b| a.min(b));
+        let max = mesh.vertices.iter().fold(Vec2::splat(f32::NEG_INFINITY), |a, &b| a.max(b));
+        (min, max)
+    }
+
+    #[test]
+    fn horizontal_and_vertical_segments() {
+        for points in [[(0.0, 0.0), (1.0, 0.0)], [(0.0, 0.0), (0.0, 1.0)]] {
+            let mut mesh = Mesh::default();
+            stroke(&points, false, &style(Join::Miter, Cap::Butt), &mut mesh);
+            assert!(mesh.vertices.iter().all(|p| p.is_finite()));
+            let (min, max) = bounds(&mesh);
+            let size = max - min;
+            assert!((size.x * size.y - 0.2).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn caps_extend_open_lines() {
+        let mut mesh = Mesh::default();
+        stroke(&[(0.0, 0.0), (1.0, 0.0)], false, &style(Join::Miter, Cap::Square), &mut mesh);
+        let (min, max) = bounds(&mesh);
+        assert!((min.x + 0.1).abs() < 1e-5 && (max.x - 1.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn miter_reaches_corner_and_falls_back_to_bevel() {
+        let corner = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+        let mut mesh = Mesh::default();
+        stroke(&corner, false, &style(Join::Miter, Cap::Butt), &mut mesh);
+        assert!(mesh.vertices.iter().any(|p| p.distance(Vec2::new(1.1, -0.1)) < 1e-5));
+
+        let spike = [(0.0, 0.0), (1.0, 0.0), (0.0, 0.05)];
+        let mut mesh = Mesh::default();
+        stroke(&spike, false, &style(Join::Miter, Cap::Butt), &mut mesh);
+        let (_, max) = bounds(&mesh);
+        assert!(max.x < 1.2);
+    }
+
+    #[test]
+    fn corners_are_covered_once() {
+        let corner = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+        let mut mesh = Mesh::default();
+        stroke(&corner, false, &style(Join::Miter, Cap::Butt), &mut mesh);
+        assert!((mesh.area() - 0.4).abs() < 1e-4);
+
+        let mut mesh = Mesh::default();
+        stroke(&corner, false, &style(Join::Round, Cap::Butt), &mut mesh);
+        assert!(mesh.area() < 0.4 && mesh.area() > 0.39);
+    }
+
+    #[test]
+    fn zero_length_segments() {
+        let mut mesh = Mesh::default();
+        stroke(&[(0.5, 0.5), (0.5, 0.5)], false, &style(Join::Round, Cap::Butt), &mut mesh);
+        assert!(mesh.triangles.is_empty());
+
+        stroke(&[(0.5, 0.5), (0.5, 0.5)], false, &style(Join::Round, Cap::Round), &mut mesh);
+        let (min, max) = bounds(&mesh);
+        assert!((max - min - Vec2::splat(0.2)).abs().max_element() < 1e-5);
+
+        let mut mesh = Mesh::default();
+        stroke(&[(0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (1.0, 1.0)], false, &style(Join::Bevel, Cap::Butt), &mut mesh);
+        assert!(mesh.vertices.iter().all(|p| p.is_finite()));
+    }
+}
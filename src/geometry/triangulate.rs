@@ -0,0 +1,238 @@
+use std::fmt;
+
+use glam::Vec2;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    SelfIntersecting,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SelfIntersecting => write!(f, "polygon intersects itself"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Splits a simple polygon into triangles by ear clipping.
+///
+/// Works for convex and concave polygons in either winding, and skips duplicate and collinear points.
+/// The returned triangles index into `points` and are always counter-clockwise.
+pub fn triangulate(points: &[(f32, f32)]) -> Result<Vec<[usize; 3]>, Error> {
+    let p = |i: usize| Vec2::from(points[i]);
+
+    let mut polygon: Vec<usize> = Vec::with_capacity(points.len());
+    for i in 0..points.len() {
+        if polygon.last().is_none_or(|&last| p(last) != p(i)) {
+            polygon.push(i);
+        }
+    }
+    while polygon.len() > 1 && p(polygon[0]) == p(*polygon.last().unwrap()) {
+        polygon.pop();
+    }
+    // 共线的点不影响形状，先去掉以免产生面积为零的三角形
+    let mut i = 0;
+    while polygon.len() >= 3 && i < polygon.len() {
+        let n = polygon.len();
+        if is_collinear(p(polygon[(i + n - 1) % n]), p(polygon[i]), p(polygon[(i + 1) % n])) {
+            polygon.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+    if polygon.len() < 3 {
+        return Ok(Vec::new());
+    }
+    if self_intersecting(&polygon.iter().map(|&i| p(i)).collect::<Vec<_>>()) {
+        return Err(Error::SelfIntersecting);
+    }
+
+    let area = signed_area(polygon.iter().map(|&i| p(i)));
+    if area == 0.0 {
+        return Ok(Vec::new());
+    }
+    if area < 0.0 {
+        polygon.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(polygon.len() - 2);
+    let mut i = 0;
+    let mut since_last_ear = 0;
+    while polygon.len() > 3 {
+        let n = polygon.len();
+        let (prev, cur, next) = (polygon[(i + n - 1) % n], polygon[i % n], polygon[(i + 1) % n]);
+        let turn = cross(p(prev), p(cur), p(next));
+        let ear = if turn <= 0.0 && is_collinear(p(prev), p(cur), p(next)) {
+            // 切掉耳朵后可能出现新的共线点
+            Some(false)
+        } else if turn > 0.0 && !polygon.iter().any(|&other| {
+            other != prev && other != cur && other != next
+                && p(other) != p(prev) && p(other) != p(cur) && p(other) != p(next)
+                && in_triangle(p(other), p(prev), p(cur), p(next))
+        }) {
+            Some(true)
+        } else {
+            None
+        };
+        match ear {
+            Some(emit) => {
+                if emit {
+                    triangles.push([prev, cur, next]);
+                }
+                polygon.remove(i % n);
+                since_last_ear = 0;
+            }
+            None => {
+                since_last_ear += 1;
+                // 浮点误差可能导致找不到耳朵，此时强行切掉当前顶点以保证结束
+                if since_last_ear > n {
+                    triangles.push([prev, cur, next]);
+                    polygon.remove(i % n);
+                    since_last_ear = 0;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        if i >= polygon.len() {
+            i = 0;
+        }
+    }
+    if cross(p(polygon[0]), p(polygon[1]), p(polygon[2])) > 0.0 {
+        triangles.push([polygon[0], polygon[1], polygon[2]]);
+    }
+    Ok(triangles)
+}
+
+pub fn signed_area(points: impl IntoIterator<Item = Vec2>) -> f32 {
+    let points: Vec<Vec2> = points.into_iter().collect();
+    let n = points.len();
+    (0..n).map(|i| points[i].perp_dot(points[(i + 1) % n])).sum::<f32>() / 2.0
+}
+
+fn cross(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    (b - a).perp_dot(c - b)
+}
+
+fn is_collinear(a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let scale = (b - a).length() * (c - b).length();
+    cross(a, b, c).abs() <= scale * 1e-6
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(p - a) >= 0.0 && (c - b).perp_dot(p - b) >= 0.0 && (a - c).perp_dot(p - c) >= 0.0
+}
+
+/// Whether two non-adjacent edges of the closed polygon touch or cross.
+fn self_intersecting(points: &[Vec2]) -> bool {
+    let n = points.len();
+    for i in 0..n {
+        let (a1, a2) = (points[i], points[(i + 1) % n]);
+        for j in i + 1..n {
+            if j == i + 1 || (i == 0 && j == n - 1) {
+                continue;
+            }
+            let (b1, b2) = (points[j], points[(j + 1) % n]);
+            if segments_intersect(a1, a2, b1, b2) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn segments_intersect(a1: Vec2, a2: Vec2, b1: Vec2, b2: Vec2) -> bool {
+    let d1 = (a2 - a1).perp_dot(b1 - a1);
+    let d2 = (a2 - a1).perp_dot(b2 - a1);
+    let d3 = (b2 - b1).perp_dot(a1 - b1);
+    let d4 = (b2 - b1).perp_dot(a2 - b1);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+    let on_segment = |p: Vec2, s1: Vec2, s2: Vec2| p.cmpge(s1.min(s2)).all() && p.cmple(s1.max(s2)).all();
+    (d1 == 0.0 && on_segment(b1, a1, a2))
+        || (d2 == 0.0 && on_segment(b2, a1, a2))
+        || (d3 == 0.0 && on_segment(a1, b1, b2))
+        || (d4 == 0.0 && on_segment(a2, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles_area(points: &[(f32, f32)], triangles: &[[usize; 3]]) -> f32 {
+        triangles.iter()
+            .map(|t| signed_area(t.iter().map(|&i| Vec2::from(points[i]))))
+            .sum()
+    }
+
+    fn assert_area_matches(points: &[(f32, f32)]) {
+        let triangles = triangulate(points).unwrap();
+        let expected = signed_area(points.iter().map(|&p| p.into())).abs();
+        let actual = triangles_area(points, &triangles);
+        assert!((expected - actual).abs() < 1e-4, "expected area {expected}, got {actual}");
+        for t in &triangles {
+            assert!(signed_area(t.iter().map(|&i| Vec2::from(points[i]))) > 0.0);
+        }
+    }
+
+    #[test]
+    fn convex() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        assert_area_matches(&square);
+        assert_eq!(triangulate(&square).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn clockwise() {
+        assert_area_matches(&[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)]);
+    }
+
+    #[test]
+    fn concave() {
+        assert_area_matches(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)]);
+        let star: Vec<(f32, f32)> = (0..10)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::PI / 5.0;
+                let r = if i % 2 == 0 { 1.0 } else { 0.4 };
+                (angle.cos() * r, angle.sin() * r)
+            })
+            .collect();
+        assert_area_matches(&star);
+        assert_eq!(triangulate(&star).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn comb() {
+        let comb = [
+            (0.0, 0.0), (7.0, 0.0), (7.0, 3.0), (6.0, 3.0), (6.0, 1.0), (5.0, 1.0), (5.0, 3.0), (4.0, 3.0),
+            (4.0, 1.0), (3.0, 1.0), (3.0, 3.0), (2.0, 3.0), (2.0, 1.0), (1.0, 1.0), (1.0, 3.0), (0.0, 3.0),
+        ];
+        assert_area_matches(&comb);
+    }
+
+    #[test]
+    fn collinear_and_duplicate_points() {
+        let points = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 2.0), (0.0, 2.0), (0.0, 1.0), (0.0, 0.0)];
+        assert_area_matches(&points);
+        assert_eq!(triangulate(&points).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert!(triangulate(&[(0.0, 0.0), (1.0, 1.0)]).unwrap().is_empty());
+        assert!(triangulate(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn self_intersecting_is_rejected() {
+        let bowtie = [(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0)];
+        assert_eq!(triangulate(&bowtie), Err(Error::SelfIntersecting));
+        let figure_eight = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, -1.0), (0.0, 1.0)];
+        assert_eq!(triangulate(&figure_eight), Err(Error::SelfIntersecting));
+    }
+}
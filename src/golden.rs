@@ -0,0 +1,119 @@
+//! Renders the documents in `tests/golden` offscreen and compares them with the PNGs next to them.
+//!
+//! Run with `DRAWING_REGENERATE_GOLDEN=1` to rewrite the PNGs after an intentional change. Failed
+//! comparisons leave the actual image and a diff, with differing pixels in red, under `target/golden`.
+//!
+//! The PNGs were rendered by llvmpipe from Mesa 22.3 through the GL backend, on a tree where the
+//! whole crate builds. Without a software adapter the test fails; set `DRAWING_SKIP_GOLDEN=1` to skip it.
+
+use std::{fs::{self, File}, io::BufReader, path::{Path, PathBuf}};
+
+use crate::{document::Document, headless::{self, Headless}};
+
+const REGENERATE_VAR: &str = "DRAWING_REGENERATE_GOLDEN";
+const SKIP_VAR: &str = "DRAWING_SKIP_GOLDEN";
+const SIZE: (u32, u32) = (256, 144);
+/// Largest difference allowed in any channel of a pixel.
+const TOLERANCE: u8 = 3;
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn read_png(path: &Path) -> Result<(Vec<u8>, u32, u32), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut reader = png::Decoder::new(BufReader::new(file)).read_info().map_err(|e| e.to_string())?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+    if (info.color_type, info.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+        return Err(format!("expected 8-bit RGBA, found {:?} {:?}", info.color_type, info.bit_depth));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok((pixels, info.width, info.height))
+}
+
+/// Counts the pixels differing by more than `TOLERANCE` and draws them red over a dimmed copy of `actual`.
+fn compare(expected: &[u8], actual: &[u8]) -> (usize, Vec<u8>) {
+    let mut mismatches = 0;
+    let mut diff = Vec::with_capacity(actual.len());
+    for (e, a) in expected.chunks(4).zip(actual.chunks(4)) {
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > TOLERANCE) {
+            mismatches += 1;
+            diff.extend([255, 0, 0, 255]);
+        } else {
+            let luma = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 9) as u8;
+            diff.extend([luma, luma, luma, 255]);
+        }
+    }
+    (mismatches, diff)
+}
+
+fn enabled(var: &str) -> bool {
+    std::env::var_os(var).is_some_and(|v| v != "0")
+}
+
+fn check(headless: &Headless, document: &Path, regenerate: bool) -> Result<(), String> {
+    let (width, height) = SIZE;
+    let golden = document.with_extension("png");
+    let actual = headless.render(Document::load(document).map_err(|e| e.to_string())?, width, height)
+        .map_err(|e| e.to_string())?;
+    if regenerate {
+        return headless::write_png(&actual, width, height, &golden).map_err(|e| e.to_string());
+    }
+    let (expected, w, h) = read_png(&golden)
+        .map_err(|e| format!("cannot read {}: {e}, run with {REGENERATE_VAR}=1 to create it", golden.display()))?;
+    if (w, h) != SIZE {
+        return Err(format!("{} is {w}x{h} instead of {width}x{height}", golden.display()));
+    }
+    let (mismatches, diff) = compare(&expected, &actual);
+    if mismatches == 0 {
+        return Ok(());
+    }
+    let out = root().join("target/golden");
+    fs::create_dir_all(&out).map_err(|e| e.to_string())?;
+    let name = document.file_stem().unwrap().to_string_lossy();
+    let write = |suffix: &str, pixels: &[u8]| {
+        let path = out.join(format!("{name}.{suffix}.png"));
+        headless::write_png(pixels, width, height, &path).map(|()| path).map_err(|e| e.to_string())
+    };
+    write("actual", &actual)?;
+    let diff = write("diff", &diff)?;
+    Err(format!("{mismatches} pixels differ, see {}", diff.display()))
+}
+
+#[test]
+fn golden_images() {
+    let headless = match Headless::software() {
+        Ok(headless) => headless,
+        Err(headless::Error::NoAdapter) if enabled(SKIP_VAR) => {
+            eprintln!("skipping golden images: no software adapter");
+            return;
+        }
+        Err(headless::Error::NoAdapter) => panic!("no software adapter such as llvmpipe, set {SKIP_VAR}=1 to skip"),
+        Err(e) => panic!("{e}"),
+    };
+    let regenerate = enabled(REGENERATE_VAR);
+    let mut documents: Vec<PathBuf> = fs::read_dir(root().join("tests/golden")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "ron"))
+        .collect();
+    documents.sort();
+    assert!(!documents.is_empty());
+
+    let failures: Vec<String> = documents.iter()
+        .filter_map(|document| {
+            let name = document.file_name().unwrap().to_string_lossy().into_owned();
+            check(&headless, document, regenerate).err().map(|e| format!("{name}: {e}"))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn tolerance_per_channel() {
+    let expected = [10, 20, 30, 255, 0, 0, 0, 255];
+    let actual = [13, 17, 30, 255, 0, 0, 4, 255];
+    let (mismatches, diff) = compare(&expected, &actual);
+    assert_eq!(mismatches, 1);
+    assert_eq!(&diff[4..], &[255, 0, 0, 255]);
+}
@@ -0,0 +1,14 @@
+use app_surface::AppSurface;
+
+/// What the layers need to build and update their GPU resources, with or without a window.
+pub struct Gpu<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub config: &'a wgpu::SurfaceConfiguration,
+}
+
+impl<'a> From<&'a AppSurface> for Gpu<'a> {
+    fn from(app: &'a AppSurface) -> Self {
+        Self { device: &app.device, queue: &app.queue, config: &app.config }
+    }
+}
@@ -0,0 +1,244 @@
+//! Rendering documents without a window, e.g. on a software adapter such as llvmpipe or lavapipe.
+
+use std::{fmt, fs::File, io::{self, BufWriter}, iter, path::Path};
+
+use crate::{base_shape, camera::Camera, document::Document, gpu::Gpu, ui};
+
+// 没有 sRGB 变体的格式：GL 后端不支持 view_formats，在回读时再做 sRGB 编码
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BYTES_PER_PIXEL: u32 = 8;
+/// Tolerance for flattening curves when framing the document.
+const TOLERANCE: f32 = 1e-3;
+
+pub struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+impl Headless {
+    /// Picks an adapter from `WGPU_BACKEND` (all backends by default), falling back to a software one.
+    pub fn new() -> Result<Self, Error> {
+        let instance = instance();
+        let adapter = request_adapter(&instance, false).or_else(|| request_adapter(&instance, true));
+        Self::with_adapter(adapter.ok_or(Error::NoAdapter)?)
+    }
+    /// Only accepts a CPU adapter such as llvmpipe, whose images do not depend on the graphics card.
+    #[cfg(test)]
+    pub fn software() -> Result<Self, Error> {
+        let adapter = request_adapter(&instance(), true)
+            .filter(|adapter| adapter.get_info().device_type == wgpu::DeviceType::Cpu);
+        Self::with_adapter(adapter.ok_or(Error::NoAdapter)?)
+    }
+    fn with_adapter(adapter: wgpu::Adapter) -> Result<Self, Error> {
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("headless"),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }, None))?;
+        Ok(Self { device, queue })
+    }
+
+    /// Draws `document` zoomed to fit its shapes the way the window shows it, without the cursor, vertex markers, grid and help text, and returns tightly packed sRGB RGBA8 rows.
+    pub fn render(&self, document: Document, width: u32, height: u32) -> Result<Vec<u8>, Error> {
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let gpu = Gpu { device: &self.device, queue: &self.queue, config: &config };
+
+        let mut base_shape = base_shape::State::new(&gpu);
+        let mut camera = Camera::new(width, height);
+        // 按图形取景，空文档保持默认视图
+        if let Some((min, max)) = document.painted_bounds(TOLERANCE) {
+            camera.fit(min.into(), max.into());
+        }
+        let mut ui = ui::State::new(&gpu);
+        ui.resize_view(&gpu, &camera);
+        ui.set_document(document);
+        ui.hide_cursor();
+        ui.set_markers_visible(false);
+        ui.set_help_visible(false);
+        ui.set_grid_visible(false);
+        base_shape.update(gpu.queue, &camera);
+        ui.set_scale(&camera);
+        ui.update(&gpu, &camera);
+
+        let extent = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let target = utils::load_texture::empty(
+            gpu.device, FORMAT, extent, None,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            Some("offscreen"),
+        );
+        // 每行字节数须按 256 对齐
+        let unpadded = width * BYTES_PER_PIXEL;
+        let padded = unpadded.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Offscreen Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.tex_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(crate::CLEAR_COLOR),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            base_shape.draw(&mut render_pass);
+            ui.draw(&mut render_pass);
+        }
+        encoder.copy_texture_to_buffer(
+            target.tex.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            extent,
+        );
+        gpu.queue.submit(iter::once(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| sender.send(result).unwrap());
+        gpu.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap()?;
+
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in data.chunks(padded as usize) {
+            for channel in row[..unpadded as usize].chunks(2).enumerate() {
+                let value = f16_to_f32(u16::from_le_bytes([channel.1[0], channel.1[1]]));
+                // 第四个分量是 alpha，不做 gamma 编码
+                let value = if channel.0 % 4 == 3 { value } else { linear_to_srgb(value) };
+                pixels.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        drop(data);
+        readback.unmap();
+        Ok(pixels)
+    }
+}
+
+fn instance() -> wgpu::Instance {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    wgpu::Instance::new(wgpu::InstanceDescriptor { backends, ..Default::default() })
+}
+
+fn request_adapter(instance: &wgpu::Instance, force_fallback_adapter: bool) -> Option<wgpu::Adapter> {
+    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        force_fallback_adapter,
+        compatible_surface: None,
+    }))
+}
+
+/// Renders `document` at `width` × `height` pixels into a PNG file.
+pub fn render_png(document: Document, width: u32, height: u32, path: impl AsRef<Path>) -> Result<(), Error> {
+    let pixels = Headless::new()?.render(document, width, height)?;
+    write_png(&pixels, width, height, path)
+}
+
+pub fn write_png(pixels: &[u8], width: u32, height: u32, path: impl AsRef<Path>) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(())
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f => if mantissa == 0.0 { f32::INFINITY } else { f32::NAN },
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+    Map(wgpu::BufferAsyncError),
+    Io(io::Error),
+    Png(png::EncodingError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoAdapter => write!(f, "no graphics adapter found, not even a software one"),
+            Error::Device(e) => write!(f, "cannot create device: {e}"),
+            Error::Map(e) => write!(f, "cannot read back the image: {e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Png(e) => write!(f, "cannot encode PNG: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::Device(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Error::Map(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Self {
+        Error::Png(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0), 0.0);
+    }
+}
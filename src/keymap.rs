@@ -0,0 +1,511 @@
+//! Which keys and mouse buttons run which commands.
+//!
+//! The defaults can be changed in a RON file that maps command names to lists of bindings, e.g.
+//! `{"new-path": ["N"], "save": ["Ctrl+S", "Ctrl+K S"], "delete": []}`. A binding is one or more
+//! space separated strokes pressed one after the other, each a key or mouse button after optional
+//! `Ctrl+`, `Alt+`, `Shift+` and `Super+`. Commands left out of the file, and those with a binding
+//! that cannot be read or that is the start of another binding or starts with one, keep their
+//! default bindings; an empty list unbinds a command.
+
+use std::{collections::BTreeMap, fmt, io, path::{Path, PathBuf}, str::FromStr};
+
+use winit::event::{ModifiersState, MouseButton, VirtualKeyCode};
+
+use crate::command::{Command, VIEWS};
+
+/// Path of the keymap file, overriding the one in the user's config directory.
+pub const PATH_VAR: &str = "DRAWING_KEYMAP";
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+}
+
+impl From<ModifiersState> for Modifiers {
+    fn from(state: ModifiersState) -> Self {
+        Self { ctrl: state.ctrl(), alt: state.alt(), shift: state.shift(), logo: state.logo() }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// A single press of a key or mouse button with the modifiers held at the time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Stroke {
+    pub modifiers: Modifiers,
+    pub input: Input,
+}
+
+impl Stroke {
+    pub fn key(key: VirtualKeyCode, modifiers: Modifiers) -> Self {
+        Self { modifiers, input: Input::Key(key) }
+    }
+    pub fn mouse(button: MouseButton, modifiers: Modifiers) -> Self {
+        Self { modifiers, input: Input::Mouse(button) }
+    }
+    fn is_modifier(&self) -> bool {
+        use VirtualKeyCode::*;
+        matches!(self.input, Input::Key(LControl | RControl | LShift | RShift | LAlt | RAlt | LWin | RWin))
+    }
+}
+
+const LETTERS: [VirtualKeyCode; 26] = {
+    use VirtualKeyCode::*;
+    [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z]
+};
+const DIGITS: [VirtualKeyCode; 10] = {
+    use VirtualKeyCode::*;
+    [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+};
+const NUMPAD: [VirtualKeyCode; 10] = {
+    use VirtualKeyCode::*;
+    [Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9]
+};
+const FUNCTION: [VirtualKeyCode; 12] = {
+    use VirtualKeyCode::*;
+    [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12]
+};
+const NAMED: &[(&str, VirtualKeyCode)] = {
+    use VirtualKeyCode::*;
+    &[
+        ("Escape", Escape), ("Enter", Return), ("Tab", Tab), ("Space", Space), ("Backspace", Back),
+        ("Delete", Delete), ("Insert", Insert), ("Home", Home), ("End", End), ("PageUp", PageUp),
+        ("PageDown", PageDown), ("Left", Left), ("Right", Right), ("Up", Up), ("Down", Down),
+        ("[", LBracket), ("]", RBracket), (",", Comma), (".", Period), ("-", Minus), ("=", Equals), ("+", Plus),
+        ("/", Slash), ("\\", Backslash), (";", Semicolon), ("'", Apostrophe), ("`", Grave),
+        ("Num+", NumpadAdd), ("Num-", NumpadSubtract), ("Num*", NumpadMultiply), ("Num/", NumpadDivide),
+        ("Num.", NumpadDecimal), ("NumEnter", NumpadEnter),
+    ]
+};
+
+fn key_name(key: VirtualKeyCode) -> Option<String> {
+    let find = |keys: &[VirtualKeyCode]| keys.iter().position(|&k| k == key);
+    if let Some(i) = find(&LETTERS) {
+        return Some(char::from(b'A' + i as u8).to_string());
+    }
+    if let Some(i) = find(&DIGITS) {
+        return Some(i.to_string());
+    }
+    if let Some(i) = find(&NUMPAD) {
+        return Some(format!("Num{i}"));
+    }
+    if let Some(i) = find(&FUNCTION) {
+        return Some(format!("F{}", i + 1));
+    }
+    NAMED.iter().find(|&&(_, k)| k == key).map(|(name, _)| name.to_string())
+}
+
+fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    let upper = name.to_ascii_uppercase();
+    match upper.as_bytes() {
+        [c @ b'A'..=b'Z'] => return Some(LETTERS[(c - b'A') as usize]),
+        [c @ b'0'..=b'9'] => return Some(DIGITS[(c - b'0') as usize]),
+        [b'N', b'U', b'M', c @ b'0'..=b'9'] => return Some(NUMPAD[(c - b'0') as usize]),
+        [b'F', ..] => {
+            if let Some(n) = upper[1..].parse::<usize>().ok().filter(|n| (1..=FUNCTION.len()).contains(n)) {
+                return Some(FUNCTION[n - 1]);
+            }
+        }
+        _ => {}
+    }
+    if upper == "ESC" {
+        return Some(VirtualKeyCode::Escape);
+    }
+    NAMED.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|&(_, key)| key)
+}
+
+impl FromStr for Stroke {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut rest = s;
+        // `Ctrl++` 是 Ctrl 加上加号键
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            let flag = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.ctrl,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "super" | "cmd" | "logo" => &mut modifiers.logo,
+                _ => break,
+            };
+            *flag = true;
+            rest = key;
+        }
+        let button = match rest.to_ascii_lowercase().as_str() {
+            "mouseleft" => Some(MouseButton::Left),
+            "mouseright" => Some(MouseButton::Right),
+            "mousemiddle" => Some(MouseButton::Middle),
+            name => name.strip_prefix("mouse").and_then(|n| n.parse().ok()).map(MouseButton::Other),
+        };
+        let input = match button {
+            Some(button) => Input::Mouse(button),
+            None => Input::Key(parse_key(rest).ok_or_else(|| format!("unknown key {rest:?}"))?),
+        };
+        Ok(Self { modifiers, input })
+    }
+}
+
+impl fmt::Display for Stroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Modifiers { ctrl, alt, shift, logo } = self.modifiers;
+        for (held, name) in [(ctrl, "Ctrl"), (alt, "Alt"), (shift, "Shift"), (logo, "Super")] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.input {
+            Input::Key(key) => match key_name(key) {
+                Some(name) => f.write_str(&name),
+                None => write!(f, "{key:?}"),
+            },
+            Input::Mouse(MouseButton::Left) => f.write_str("MouseLeft"),
+            Input::Mouse(MouseButton::Right) => f.write_str("MouseRight"),
+            Input::Mouse(MouseButton::Middle) => f.write_str("MouseMiddle"),
+            Input::Mouse(MouseButton::Other(n)) => write!(f, "Mouse{n}"),
+        }
+    }
+}
+
+/// Strokes pressed one after the other.
+pub fn parse_binding(s: &str) -> Result<Vec<Stroke>, String> {
+    let strokes = s.split_whitespace().map(str::parse).collect::<Result<Vec<Stroke>, _>>()?;
+    if strokes.is_empty() {
+        return Err("empty binding".into());
+    }
+    Ok(strokes)
+}
+
+pub fn format_binding(strokes: &[Stroke]) -> String {
+    strokes.iter().map(Stroke::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn defaults() -> Vec<(Command, Vec<String>)> {
+    use Command::*;
+    let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+    let mut defaults = vec![
+        (NewPath, keys(&["Z"])),
+        (FillPath, keys(&["F"])),
+        (AddHole, keys(&["H"])),
+        (ToggleFillRule, keys(&["R"])),
+        (Delete, keys(&["D", "Delete"])),
+        (ControlPoint, keys(&["Q"])),
+        (Arc, keys(&["A"])),
+        (NextJoin, keys(&["J"])),
+        (NextCap, keys(&["K"])),
+        (Thinner, keys(&["["])),
+        (Wider, keys(&["]"])),
+        (StrokeColor, keys(&["C"])),
+        (FillColor, keys(&["V"])),
+        (LessOpacity, keys(&[","])),
+        (MoreOpacity, keys(&["."])),
+        (AddLabel, keys(&["T"])),
+        (AlignLabel, keys(&["L"])),
+        (NewLayer, keys(&["N"])),
+        (DeleteLayer, keys(&["Shift+Delete"])),
+        (LayerAbove, keys(&["Up"])),
+        (LayerBelow, keys(&["Down"])),
+        (RaiseLayer, keys(&["Shift+Up"])),
+        (LowerLayer, keys(&["Shift+Down"])),
+        (ToggleLayerVisibility, keys(&["Shift+H"])),
+        (ToggleLayerLock, keys(&["Shift+L"])),
+        (LessLayerOpacity, keys(&["Shift+,"])),
+        (MoreLayerOpacity, keys(&["Shift+."])),
+        (Union, keys(&["Alt+U"])),
+        (Intersection, keys(&["Alt+I"])),
+        (Difference, keys(&["Alt+D"])),
+        (Xor, keys(&["Alt+X"])),
+        (Offset, keys(&["Alt+O"])),
+        (ToggleGrid, keys(&["G"])),
+        (Deselect, keys(&["Escape", "MouseRight"])),
+        // 加号在多数键盘上要按 Shift
+        (ZoomIn, keys(&["+", "Shift++", "=", "Shift+=", "Num+"])),
+        (ZoomOut, keys(&["-", "Num-"])),
+        (RotateLeft, keys(&["PageUp"])),
+        (RotateRight, keys(&["PageDown"])),
+        (ZoomToFit, keys(&["Home"])),
+        (Save, keys(&["Ctrl+S"])),
+        (Open, keys(&["Ctrl+O"])),
+        (ExportSvg, keys(&["Ctrl+E"])),
+        (ImportSvg, keys(&["Ctrl+I"])),
+        (Console, keys(&["`"])),
+        (Undo, keys(&["Ctrl+Z"])),
+        (Redo, keys(&["Ctrl+Y", "Ctrl+Shift+Z"])),
+    ];
+    defaults.extend((1..=VIEWS).map(|n| (SaveView(n), vec![format!("Ctrl+{n}")])));
+    defaults.extend((1..=VIEWS).map(|n| (View(n), vec![n.to_string()])));
+    defaults
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<Stroke>, Command)>,
+    /// The strokes of a binding typed so far.
+    pending: Vec<Stroke>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self { bindings: Vec::new(), pending: Vec::new() };
+        for (command, bindings) in defaults() {
+            let bindings = bindings.iter().map(|binding| parse_binding(binding).expect("default bindings are valid"));
+            keymap.bindings.extend(bindings.map(|strokes| (strokes, command)));
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Reads a keymap file over the defaults; bindings that could not be used are returned
+    /// with the keymap.
+    pub fn from_ron(s: &str) -> Result<(Self, Vec<Error>), Error> {
+        let config: BTreeMap<String, Vec<String>> = ron::from_str(s)?;
+        let mut problems = Vec::new();
+        let mut configured = Vec::new();
+        for (name, bindings) in config {
+            let Some(command) = Command::from_name(&name) else {
+                problems.push(Error::UnknownCommand(name));
+                continue;
+            };
+            let parsed = bindings.iter()
+                .map(|binding| parse_binding(binding).map_err(|reason| Error::InvalidBinding {
+                    command: name.clone(),
+                    binding: binding.clone(),
+                    reason,
+                }))
+                .collect::<Result<Vec<_>, _>>();
+            match parsed {
+                Ok(parsed) => configured.push((command, name, bindings, parsed)),
+                Err(e) => problems.push(e),
+            }
+        }
+        // 一个绑定是另一个的开头时，长的那个永远按不到；这样的命令保留默认绑定，
+        // 而它的默认绑定又可能挡住别的命令，所以重复到没有冲突为止
+        let mut rejected = vec![false; configured.len()];
+        loop {
+            let mut keymap = Self::default();
+            for ((command, _, _, parsed), _) in configured.iter().zip(&rejected).filter(|(_, &rejected)| !rejected) {
+                keymap.rebind(*command, parsed.clone());
+            }
+            let mut conflicts = Vec::new();
+            for (i, (_, name, bindings, parsed)) in configured.iter().enumerate().filter(|&(i, _)| !rejected[i]) {
+                for (binding, strokes) in bindings.iter().zip(parsed) {
+                    if let Some((other, command)) = keymap.overlap(strokes) {
+                        let reason = if other.len() < strokes.len() {
+                            format!("{} already runs {command}", format_binding(other))
+                        } else {
+                            format!("it is the start of {} for {command}", format_binding(other))
+                        };
+                        problems.push(Error::InvalidBinding { command: name.clone(), binding: binding.clone(), reason });
+                        conflicts.push(i);
+                    }
+                }
+            }
+            if conflicts.is_empty() {
+                return Ok((keymap, problems));
+            }
+            for i in conflicts {
+                rejected[i] = true;
+            }
+        }
+    }
+    pub fn load(path: &Path) -> Result<(Self, Vec<Error>), Error> {
+        let s = std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+        Self::from_ron(&s)
+    }
+    /// Loads the keymap file if there is one, reporting what could not be used.
+    pub fn load_or_default() -> Self {
+        let configured = std::env::var_os(PATH_VAR).filter(|path| !path.is_empty()).map(PathBuf::from);
+        let Some(path) = configured.clone().or_else(default_path) else {
+            return Self::default();
+        };
+        // 没有配置文件时不必提示
+        if configured.is_none() && !path.exists() {
+            return Self::default();
+        }
+        match Self::load(&path) {
+            Ok((keymap, problems)) => {
+                for problem in problems {
+                    eprintln!("{}: {problem}, keeping the default", path.display());
+                }
+                keymap
+            }
+            Err(e) => {
+                eprintln!("{}: {e}, using the default keys", path.display());
+                Self::default()
+            }
+        }
+    }
+    /// Replaces the bindings of `command`, taking them away from any other command.
+    pub fn rebind(&mut self, command: Command, bindings: Vec<Vec<Stroke>>) {
+        self.bindings.retain(|(strokes, c)| *c != command && !bindings.contains(strokes));
+        self.bindings.extend(bindings.into_iter().map(|strokes| (strokes, command)));
+    }
+    /// A binding that `strokes` starts with or that starts with `strokes`, so that one of them
+    /// could never be pressed.
+    fn overlap(&self, strokes: &[Stroke]) -> Option<&(Vec<Stroke>, Command)> {
+        self.bindings.iter().find(|(other, _)| {
+            other.len() != strokes.len() && (other.starts_with(strokes) || strokes.starts_with(other))
+        })
+    }
+    /// The first binding of `command`, as shown in the help.
+    pub fn binding(&self, command: Command) -> Option<&[Stroke]> {
+        self.bindings.iter().find(|(_, c)| *c == command).map(|(strokes, _)| strokes.as_slice())
+    }
+    /// Whether the strokes so far are the start of a longer binding.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    /// Takes a stroke, returns the command once a whole binding has been pressed.
+    pub fn press(&mut self, stroke: Stroke) -> Option<Command> {
+        if stroke.is_modifier() {
+            return None;
+        }
+        self.pending.push(stroke);
+        loop {
+            if let Some((_, command)) = self.bindings.iter().find(|(strokes, _)| *strokes == self.pending) {
+                self.pending.clear();
+                return Some(*command);
+            }
+            if self.bindings.iter().any(|(strokes, _)| strokes.starts_with(&self.pending)) {
+                return None;
+            }
+            if self.pending.len() == 1 {
+                self.pending.clear();
+                return None;
+            }
+            // 前面的键凑不成绑定，从这一下重新开始
+            self.pending = vec![stroke];
+        }
+    }
+}
+
+/// `drawing/keymap.ron` in the config directory of the platform.
+fn default_path() -> Option<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env("XDG_CONFIG_HOME").or_else(|| env("HOME").map(|home| home.join(".config")))
+    };
+    dir.map(|dir| dir.join("drawing").join("keymap.ron"))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(ron::error::SpannedError),
+    UnknownCommand(String),
+    InvalidBinding { command: String, binding: String, reason: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "cannot read keymap {}: {e}", path.display()),
+            Error::Parse(e) => write!(f, "invalid keymap: {e}"),
+            Error::UnknownCommand(name) => write!(f, "unknown command {name}"),
+            Error::InvalidBinding { command, binding, reason } => write!(f, "cannot bind {binding:?} to {command}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ron::error::SpannedError> for Error {
+    fn from(e: ron::error::SpannedError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(s: &str) -> Stroke {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn strokes_round_trip() {
+        for s in ["Z", "Ctrl+S", "Ctrl+Shift+Z", "Ctrl++", "+", "Alt+MouseRight", "Mouse4", "Num+", "F11", "PageUp", "5"] {
+            assert_eq!(stroke(s).to_string(), s);
+        }
+        assert_eq!(stroke("control+z"), stroke("Ctrl+Z"));
+        assert_eq!(stroke("esc"), stroke("Escape"));
+        assert!("Hyper+Z".parse::<Stroke>().is_err());
+        assert!("F13".parse::<Stroke>().is_err());
+        assert!(parse_binding(" ").is_err());
+        assert_eq!(parse_binding("Ctrl+K  Ctrl+S").unwrap(), [stroke("Ctrl+K"), stroke("Ctrl+S")]);
+    }
+
+    #[test]
+    fn chords_and_fallbacks() {
+        let config = r#"{
+            "save": ["Ctrl+K S"],
+            "new-path": ["S"],
+            "open": ["Ctrl+Nope"],
+            "delete": [],
+            "explode": ["X"],
+        }"#;
+        let (mut keymap, problems) = Keymap::from_ron(config).unwrap();
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert_eq!(keymap.press(stroke("Ctrl+O")), Some(Command::Open));
+        assert_eq!(keymap.press(stroke("Ctrl+S")), None);
+        assert_eq!(keymap.press(stroke("D")), None);
+        assert_eq!(keymap.press(stroke("Z")), None);
+
+        assert_eq!(keymap.press(stroke("Ctrl+K")), None);
+        assert!(keymap.is_pending());
+        assert_eq!(keymap.press(Stroke::key(VirtualKeyCode::LShift, Modifiers::default())), None);
+        assert_eq!(keymap.press(stroke("S")), Some(Command::Save));
+        assert_eq!(keymap.press(stroke("S")), Some(Command::NewPath));
+        // 没有按完的和弦被后面的键打断
+        keymap.press(stroke("Ctrl+K"));
+        assert_eq!(keymap.press(stroke("G")), Some(Command::ToggleGrid));
+        assert!(!keymap.is_pending());
+
+        assert!(Keymap::from_ron("[1, 2]").is_err());
+        let defaults = Keymap::default();
+        assert!(defaults.bindings.iter().all(|(strokes, _)| defaults.overlap(strokes).is_none()));
+        assert_eq!(Keymap::default().binding(Command::View(3)), Some(&[stroke("3")][..]));
+    }
+
+    #[test]
+    fn bindings_hidden_by_a_prefix_are_rejected() {
+        let config = r#"{
+            "save": ["Ctrl+K S"],
+            "open": ["Ctrl+K"],
+            "export-svg": ["Ctrl+S X", "Ctrl+E"],
+            "undo": ["Ctrl+Shift+Y"],
+        }"#;
+        let (mut keymap, problems) = Keymap::from_ron(config).unwrap();
+        // Ctrl+K S 和 Ctrl+K 互相挡住，两个都不用；Ctrl+S 仍是保存
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems.iter().all(|problem| matches!(problem, Error::InvalidBinding { .. })));
+        assert_eq!(keymap.press(stroke("Ctrl+K")), None);
+        assert!(!keymap.is_pending());
+        assert_eq!(keymap.press(stroke("Ctrl+S")), Some(Command::Save));
+        assert_eq!(keymap.press(stroke("Ctrl+O")), Some(Command::Open));
+        assert_eq!(keymap.press(stroke("Ctrl+E")), Some(Command::ExportSvg));
+        assert_eq!(keymap.press(stroke("Ctrl+Shift+Y")), Some(Command::Undo));
+
+        // 留下默认绑定的命令又挡住了别的绑定
+        let config = r#"{
+            "undo": ["Ctrl+S Ctrl+S"],
+            "redo": ["Ctrl+Z X"],
+        }"#;
+        let (mut keymap, problems) = Keymap::from_ron(config).unwrap();
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert_eq!(keymap.press(stroke("Ctrl+Z")), Some(Command::Undo));
+        assert_eq!(keymap.press(stroke("Ctrl+Y")), Some(Command::Redo));
+    }
+}
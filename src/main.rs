@@ -1,43 +1,102 @@
 use std::iter;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use app_surface::{AppSurface, SurfaceFrame};
+use camera::Camera;
+use glam::{vec2, Mat4, Vec2};
+use gpu::Gpu;
 use utils::framework::{Action, run};
-use winit::{dpi::{PhysicalPosition, PhysicalSize}, window::WindowId};
-use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use winit::{dpi::PhysicalSize, window::WindowId};
+use winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, WindowEvent};
+
+use command::{Command, VIEWS};
+use keymap::{Keymap, Modifiers, Stroke};
 
 mod base_shape;
+mod camera;
+mod cli;
+mod command;
+mod document;
+mod font;
+mod geometry;
+mod gpu;
+#[cfg(test)]
+mod golden;
+mod headless;
+mod keymap;
+mod script;
 mod ui;
 pub(crate) mod uniform;
 
-
+const DEFAULT_PATH: &str = "drawing.ron";
+const HISTORY_DEPTH_VAR: &str = "DRAWING_HISTORY_DEPTH";
+const ZOOM_STEP: f32 = 1.25;
+const FIT_TOLERANCE: f32 = 1e-3;
+const ROTATION_STEP: f32 = std::f32::consts::PI / 12.0;
+/// File given to `edit` on the command line.
+static OPEN: OnceLock<PathBuf> = OnceLock::new();
+/// Name and source of the script given to `edit`, run once the window is open.
+static SCRIPT: OnceLock<(PathBuf, String)> = OnceLock::new();
+pub(crate) const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
 
 struct State {
     app: AppSurface,
     ui: ui::State,
     base_shape: base_shape::State,
-    track_cursor: PhysicalPosition<f64>,
-    old_pos: PhysicalPosition<f64>,
-    last_track: PhysicalPosition<f64>,
+    camera: Camera,
+    keymap: Keymap,
+    bookmarks: [Option<Mat4>; VIEWS],
+    track_cursor: Vec2,
+    old_pos: Vec2,
+    last_track: Vec2,
     pressed: bool,
-    ctrl: bool,
+    modifiers: Modifiers,
+    /// The last key pressed ran a command, so the character it typed is not text.
+    key_handled: bool,
+    path: PathBuf,
 }
 
 impl Action for State {
     fn new(app: AppSurface) -> Self {
 
-        let ui = ui::State::new(&app);
-        let base_shape = base_shape::State::new(&app);
+        let gpu = Gpu::from(&app);
+        let mut ui = ui::State::new(&gpu);
+        if let Some(depth) = std::env::var(HISTORY_DEPTH_VAR).ok().and_then(|depth| depth.parse().ok()) {
+            ui.document_mut().history_mut().set_depth(depth);
+        }
+        let base_shape = base_shape::State::new(&gpu);
+        let mut camera = Camera::new(app.config.width, app.config.height);
+        camera.set_scale_factor(app.view.scale_factor() as f32);
+        ui.resize_view(&gpu, &camera);
+        let keymap = Keymap::load_or_default();
+        ui.set_help(&keymap);
 
-        Self {
+        let mut state = Self {
             app,
             ui,
             base_shape,
-            track_cursor: PhysicalPosition::default(),
-            old_pos: PhysicalPosition::default(),
-            last_track: PhysicalPosition::default(),
+            camera,
+            keymap,
+            bookmarks: [None; VIEWS],
+            track_cursor: Vec2::ZERO,
+            old_pos: Vec2::ZERO,
+            last_track: Vec2::ZERO,
             pressed: false,
-            ctrl: false,
+            modifiers: Modifiers::default(),
+            key_handled: false,
+            path: PathBuf::from(DEFAULT_PATH),
+        };
+        if let Some(path) = OPEN.get() {
+            state.open_file(path);
         }
+        if let Some((path, source)) = SCRIPT.get() {
+            match state.run_script(source) {
+                Ok(()) => println!("Ran {}", path.display()),
+                Err(e) => eprintln!("{}: {e}", path.display()),
+            }
+        }
+        state
     }
     fn get_adapter_info(&self) -> wgpu::AdapterInfo {
         self.app.adapter.get_info()
@@ -47,55 +106,102 @@ impl Action for State {
         self.app.view.id()
     }
     fn resize(&mut self, size: &PhysicalSize<u32>) {
-        if self.app.config.width == size.width && self.app.config.height == size.height {
+        let scale_factor = self.app.view.scale_factor() as f32;
+        if self.app.config.width == size.width && self.app.config.height == size.height
+            && self.camera.scale_factor() == scale_factor {
             return;
         }
-        self.ui.resize_view(&self.app);
+        // 先更新 surface，各层再按新的配置调整
         self.app.resize_surface();
+        self.camera.resize(self.app.config.width, self.app.config.height);
+        self.camera.set_scale_factor(scale_factor);
+        self.ui.resize_view(&Gpu::from(&self.app), &self.camera);
     }
     fn request_redraw(&mut self) {
         self.app.view.request_redraw();
     }
     fn input(&mut self, event: &WindowEvent) -> bool {
-        if let WindowEvent::ReceivedCharacter('z') = event {
-            self.ui.new_path(false);
+        if let WindowEvent::ModifiersChanged(state) = event {
+            self.modifiers = Modifiers::from(*state);
+            // 按住 Ctrl 时按 15 度吸附，按住 Shift 时等比缩放，光标和拖动中的图形马上跟着变
+            self.ui.set_angle_snap(self.modifiers.ctrl);
+            self.ui.set_uniform_scale(self.modifiers.shift);
+            self.view_changed();
         }
-        if let WindowEvent::ReceivedCharacter('f') = event {
-            self.ui.new_path(true);
+        if let WindowEvent::KeyboardInput { input: KeyboardInput { virtual_keycode: Some(key), state: ElementState::Pressed, ..}, ..} = event {
+            // 编辑标签或输入命令时只有带 Ctrl 或 Alt 的组合键是快捷键，其余按键都是文字
+            let Modifiers { ctrl, alt, logo, .. } = self.modifiers;
+            if self.ui.is_typing() && !(ctrl || alt || logo) {
+                self.key_handled = false;
+                return true;
+            }
+            let command = self.keymap.press(Stroke::key(*key, self.modifiers));
+            self.key_handled = command.is_some() || self.keymap.is_pending();
+            if let Some(command) = command {
+                self.run(command);
+            }
         }
-        if let WindowEvent::ReceivedCharacter('d') = event {
-            self.ui.delete_path();
+        if let WindowEvent::ReceivedCharacter(c) = event {
+            // 已经当作快捷键的按键打出的字符不再输入
+            if !std::mem::take(&mut self.key_handled) && self.ui.type_char(*c) {
+                if let Some(line) = self.ui.take_console_line() {
+                    let result = self.run_script(&line);
+                    self.ui.console_result(result.map_err(|e| e.to_string()));
+                }
+                return true;
+            }
+        }
+        if let WindowEvent::MouseWheel { delta, ..} = event {
+            // 一格滚轮放大一档，触控板按像素折算
+            let steps = match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(p) => p.y as f32 / 50.0,
+            };
+            self.zoom(ZOOM_STEP.powf(steps));
         }
         if let WindowEvent::CursorMoved { position: p, ..} = event {
-            self.ui.update_cursor(&self.app.config, p.x as _, p.y as _);
-            self.track_cursor = *p;
-            if self.pressed {
-                self.base_shape.change_zoom(&self.app.config, self.last_track, self.track_cursor);
+            let p = vec2(p.x as f32, p.y as f32);
+            self.ui.update_cursor(&self.camera, p);
+            self.track_cursor = p;
+            if self.pressed && !self.ui.is_dragging() {
+                self.camera.pan(self.last_track, self.track_cursor);
+                self.view_changed();
             }
-            self.last_track = *p;
+            self.last_track = p;
         }
-        if let WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, ..} = event {
-            self.old_pos = self.track_cursor;
-            self.pressed = true;
+        if let WindowEvent::MouseInput { state: ElementState::Pressed, button, ..} = event {
+            // 左键没有绑定命令时用来画图，按住 Shift 点图形时加选或减选
+            if let Some(command) = self.keymap.press(Stroke::mouse(*button, self.modifiers)) {
+                self.run(command);
+            } else if *button == MouseButton::Left && !self.keymap.is_pending()
+                && !(self.modifiers.shift && self.ui.toggle_selected()) {
+                self.old_pos = self.track_cursor;
+                self.pressed = true;
+                self.ui.document_mut().history_mut().begin_group();
+                self.ui.select();
+            }
         }
         if let WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, ..} = event {
-            if self.old_pos == self.track_cursor {
+            // 点中已有的图形时只选中它，不添加新点
+            if self.pressed && !self.ui.end_drag() && self.old_pos == self.track_cursor {
                 self.ui.push_point();
             }
-            self.pressed = false;
-        }
-        if let WindowEvent::KeyboardInput { input: KeyboardInput { virtual_keycode, state, ..}, ..} = event {
-            if virtual_keycode == &Some(VirtualKeyCode::LControl) {
-                self.ctrl = state == &ElementState::Pressed;
-                println!("Ctrl {state:?}");
+            if std::mem::take(&mut self.pressed) {
+                self.ui.document_mut().history_mut().end_group();
             }
         }
+        // 松开左键前失去焦点或移出窗口时收不到松开事件，直接结束拖动
+        if matches!(event, WindowEvent::Focused(false) | WindowEvent::CursorLeft { .. }) && std::mem::take(&mut self.pressed) {
+            self.ui.end_drag();
+            self.ui.document_mut().history_mut().end_group();
+        }
         false
     }
 
     fn update(&mut self) {
-        self.base_shape.update(&self.app.queue);
-        self.ui.update(&self.app);
+        self.base_shape.update(&self.app.queue, &self.camera);
+        self.ui.set_scale(&self.camera);
+        self.ui.update(&Gpu::from(&self.app), &self.camera);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -116,18 +222,15 @@ impl Action for State {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
                         store: true,
                     },
                 })],
                 depth_stencil_attachment: None,
             });
 
+            self.ui.draw_grid(&mut render_pass);
+
             self.base_shape.draw(&mut render_pass);
 
             self.ui.draw(&mut render_pass);
@@ -140,6 +243,183 @@ impl Action for State {
     }
 }
 
+impl State {
+    fn run(&mut self, command: Command) {
+        match command {
+            Command::NewPath => self.ui.new_path(false),
+            Command::FillPath => self.ui.new_path(true),
+            Command::AddHole => self.ui.new_contour(),
+            Command::ToggleFillRule => self.ui.toggle_fill_rule(),
+            Command::Delete => self.ui.delete_path(),
+            Command::ControlPoint => self.ui.add_control_point(),
+            Command::Arc => self.ui.toggle_arc(),
+            Command::NextJoin => self.ui.update_style(|style| style.join = style.join.next()),
+            Command::NextCap => self.ui.update_style(|style| style.cap = style.cap.next()),
+            Command::StrokeColor => {
+                if !self.ui.update_label(|label| label.color = ui::next_color(label.color)) {
+                    self.ui.update_style(|style| style.stroke_color = ui::next_color(style.stroke_color));
+                }
+            }
+            Command::FillColor => self.ui.update_style(|style| style.fill_color = ui::next_color(style.fill_color)),
+            Command::LessOpacity => self.ui.update_style(|style| style.opacity = (style.opacity - 0.1).max(0.1)),
+            Command::MoreOpacity => self.ui.update_style(|style| style.opacity = (style.opacity + 0.1).min(1.0)),
+            Command::Thinner => {
+                if !self.ui.update_label(|label| label.size /= 1.25) {
+                    self.ui.update_style(|style| style.width = (style.width / 1.25).max(0.001));
+                }
+            }
+            Command::Wider => {
+                if !self.ui.update_label(|label| label.size *= 1.25) {
+                    self.ui.update_style(|style| style.width = (style.width * 1.25).clamp(0.001, 0.5));
+                }
+            }
+            Command::AddLabel => self.ui.add_label(),
+            Command::AlignLabel => {
+                self.ui.update_label(|label| label.align = label.align.next());
+            }
+            Command::NewLayer => self.ui.add_layer(),
+            Command::DeleteLayer => self.ui.remove_layer(),
+            Command::LayerAbove => self.ui.step_active_layer(1),
+            Command::LayerBelow => self.ui.step_active_layer(-1),
+            Command::RaiseLayer => self.ui.move_layer(1),
+            Command::LowerLayer => self.ui.move_layer(-1),
+            Command::ToggleLayerVisibility => self.ui.update_layer(|layer| layer.visible = !layer.visible),
+            Command::ToggleLayerLock => self.ui.update_layer(|layer| layer.locked = !layer.locked),
+            Command::LessLayerOpacity => self.ui.update_layer(|layer| layer.opacity = (layer.opacity - 0.1).max(0.1)),
+            Command::MoreLayerOpacity => self.ui.update_layer(|layer| layer.opacity = (layer.opacity + 0.1).min(1.0)),
+            Command::Union => self.ui.combine(document::Operation::Union),
+            Command::Intersection => self.ui.combine(document::Operation::Intersection),
+            Command::Difference => self.ui.combine(document::Operation::Difference),
+            Command::Xor => self.ui.combine(document::Operation::Xor),
+            Command::ToggleGrid => {
+                self.ui.toggle_grid();
+                self.view_changed();
+            }
+            Command::Deselect => self.ui.clear_selection(),
+            Command::ZoomIn => self.zoom(ZOOM_STEP),
+            Command::ZoomOut => self.zoom(1.0 / ZOOM_STEP),
+            Command::ZoomToFit => self.zoom_to_fit(),
+            Command::RotateLeft => self.rotate(ROTATION_STEP),
+            Command::RotateRight => self.rotate(-ROTATION_STEP),
+            Command::SaveView(n) => self.bookmarks[n - 1] = Some(self.camera.view()),
+            Command::View(n) => {
+                if let Some(view) = self.bookmarks[n - 1] {
+                    self.camera.set_view(view);
+                    self.view_changed();
+                }
+            }
+            Command::Save => self.save(),
+            Command::Open => self.open(),
+            Command::ExportSvg => self.export_svg(),
+            Command::ImportSvg => self.import_svg(),
+            Command::Console => self.ui.open_console(""),
+            Command::Offset => self.ui.open_console("offset "),
+            Command::Undo => self.ui.undo(),
+            Command::Redo => self.ui.redo(),
+        }
+    }
+    /// Runs a script on the document, saving files as seen in the window.
+    fn run_script(&mut self, source: &str) -> Result<(), script::Error> {
+        self.ui.run_script(source, (self.app.config.width, self.app.config.height))
+    }
+    /// Magnifies around the mouse cursor.
+    fn zoom(&mut self, factor: f32) {
+        self.camera.zoom_at(self.track_cursor, factor);
+        self.view_changed();
+    }
+    /// Turns the view around the centre of the window.
+    fn rotate(&mut self, angle: f32) {
+        let (width, height) = (self.app.config.width as f32, self.app.config.height as f32);
+        self.camera.rotate_at(vec2(width, height) / 2.0, angle);
+        self.view_changed();
+    }
+    fn zoom_to_fit(&mut self) {
+        if let Some(bounds) = self.ui.document().bounds(FIT_TOLERANCE) {
+            self.camera.fit(bounds.0.into(), bounds.1.into());
+            self.view_changed();
+        }
+    }
+    fn view_changed(&mut self) {
+        // 视图变了，光标下的文档坐标也跟着变
+        self.ui.update_cursor(&self.camera, self.track_cursor);
+    }
+    fn save(&self) {
+        match self.ui.document().save(&self.path) {
+            Ok(()) => println!("Saved {}", self.path.display()),
+            Err(e) => eprintln!("Cannot save {}: {e}", self.path.display()),
+        }
+    }
+    fn export_svg(&self) {
+        let path = self.path.with_extension("svg");
+        let config = &self.app.config;
+        match document::svg::save(self.ui.document(), config.width, config.height, &path) {
+            Ok(()) => println!("Exported {}", path.display()),
+            Err(e) => eprintln!("Cannot export {}: {e}", path.display()),
+        }
+    }
+    fn import_svg(&mut self) {
+        let path = self.path.with_extension("svg");
+        match document::svg::load(&path, self.camera.ratio()) {
+            Ok(shapes) => {
+                println!("Imported {} shapes from {}", shapes.len(), path.display());
+                self.ui.insert_shapes(shapes);
+            }
+            Err(e) => eprintln!("Cannot import {}: {e}", path.display()),
+        }
+    }
+    fn open(&mut self) {
+        match document::Document::load(&self.path) {
+            Ok(document) => {
+                self.ui.set_document(document);
+                println!("Opened {}", self.path.display());
+            }
+            Err(e) => eprintln!("Cannot open {}: {e}", self.path.display()),
+        }
+    }
+    /// Opens the file given on the command line; SVG files are imported and saved as native documents.
+    fn open_file(&mut self, path: &std::path::Path) {
+        self.path = path.with_extension("ron");
+        // 文件不存在时从空白文档开始，保存时再创建
+        if !path.exists() {
+            return;
+        }
+        match cli::load(path, self.camera.ratio()) {
+            Ok(document) => {
+                self.ui.set_document(document);
+                println!("Opened {}", path.display());
+            }
+            Err(e) => eprintln!("Cannot open {}: {e}", path.display()),
+        }
+    }
+}
+
 fn main() {
-    run::<State>(None, None);
+    match cli::Command::parse(std::env::args().skip(1)) {
+        Ok(cli::Command::Edit { file, script }) => {
+            if let Some(file) = file {
+                OPEN.set(file).unwrap();
+            }
+            // 窗口打开之前读脚本，读不了就不打开
+            if let Some(path) = script {
+                match cli::read_script(&path) {
+                    Ok(source) => SCRIPT.set((path, source)).unwrap(),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            run::<State>(None, None);
+        }
+        Ok(command) => {
+            if let Err(e) = cli::execute(command) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    }
 }
@@ -0,0 +1,451 @@
+//! A small command language for building drawings, one command per line.
+//!
+//! ```text
+//! # 橙色三角形
+//! style width=0.01 stroke=black fill=#ff8800
+//! point 0 0
+//! point 1 0
+//! point 0.5 0.8
+//! fill
+//! save "triangle.svg"
+//! ```
+//!
+//! Commands work on the path being drawn, like the keys of the editor: `point`, `quad` and `cubic`
+//! add nodes to it, `hole` starts another contour in it, and `fill`, `close` and `path` finish it
+//! filled, closed or open and start the next one. `style` changes its style, which later paths keep,
+//! `translate` moves it or the last finished path of the layer, `offset` grows that path by a
+//! distance or shrinks it for negative distances, with `miter`, `round` or `square` corners that
+//! default to its join, `delete` clears the path being drawn or removes the last one, `layer` draws
+//! into the layer with the given name, adding it above the active layer if there is none, and `save`
+//! writes the drawing to a `.ron`, `.svg` or `.png` file. A word starting with `#` starts a comment.
+//! Run from the editor, `translate` and `offset` change the selected shape instead.
+
+use std::{fmt, path::PathBuf};
+
+use crate::cli;
+use crate::document::{Cap, Color, Corner, Document, Join, Node, Segment};
+
+pub const COMMANDS: &[&str] = &["path", "point", "quad", "cubic", "hole", "fill", "close", "style", "translate", "offset", "delete", "layer", "save"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Instruction {
+    Path,
+    Node(Node),
+    Hole,
+    Fill,
+    Close,
+    Style(Vec<Setting>),
+    Translate((f32, f32)),
+    Offset(f32, Option<Corner>),
+    Delete,
+    Layer(String),
+    Save(PathBuf),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Setting {
+    Width(f32),
+    Join(Join),
+    Cap(Cap),
+    MiterLimit(f32),
+    Stroke(Color),
+    Fill(Color),
+    Opacity(f32),
+}
+
+/// A word of a line and the column it starts at, counted in characters from 1.
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+/// Runs `source` on `document` as one undo step; `size` is the view in pixels that `save` uses for
+/// SVG and PNG files.
+///
+/// The whole script is read before anything runs, so a mistake anywhere leaves the drawing as it
+/// was; commands that fail while running stop the script and keep what was done before them.
+pub fn run(source: &str, document: &mut Document, size: (u32, u32)) -> Result<(), Error> {
+    run_on(source, document, size, None)
+}
+
+/// Like `run`, but `translate` and `offset` change the shape at `selected` when there is one.
+pub fn run_on(source: &str, document: &mut Document, size: (u32, u32), mut selected: Option<usize>) -> Result<(), Error> {
+    let instructions = parse(source)?;
+    document.history_mut().begin_group();
+    let result = instructions.into_iter()
+        .try_for_each(|(line, column, instruction)| {
+            execute(instruction, document, size, &mut selected).map_err(|problem| Error { line, column, problem })
+        });
+    document.history_mut().end_group();
+    result
+}
+
+fn parse(source: &str) -> Result<Vec<(usize, usize, Instruction)>, Error> {
+    let mut instructions = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let error = |(column, problem)| Error { line: index + 1, column, problem };
+        let tokens = tokenize(line).map_err(error)?;
+        let Some((command, arguments)) = tokens.split_first() else {
+            continue;
+        };
+        let instruction = parse_instruction(command, arguments).map_err(error)?;
+        instructions.push((index + 1, command.column, instruction));
+    }
+    Ok(instructions)
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, (usize, Problem)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().zip(1..).peekable();
+    while let Some(&(c, column)) = chars.peek() {
+        if c == '#' {
+            break;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut text = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(('"', _)) => break,
+                    Some(('\\', _)) => match chars.next() {
+                        Some((c, _)) => text.push(c),
+                        None => return Err((column, Problem::UnclosedQuote)),
+                    },
+                    Some((c, _)) => text.push(c),
+                    None => return Err((column, Problem::UnclosedQuote)),
+                }
+            }
+        } else {
+            // 只有单词开头的 # 是注释，颜色里的 # 不是
+            while let Some(&(c, _)) = chars.peek().filter(|(c, _)| !c.is_whitespace()) {
+                text.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(Token { text, column });
+    }
+    Ok(tokens)
+}
+
+fn parse_instruction(command: &Token, arguments: &[Token]) -> Result<Instruction, (usize, Problem)> {
+    // 参数个数不对时指向多出来的参数，或者命令后面缺参数的地方
+    let count = |expected: usize, what: &'static str| {
+        if let Some(extra) = arguments.get(expected) {
+            return Err((extra.column, Problem::UnexpectedArgument(extra.text.clone())));
+        }
+        if arguments.len() < expected {
+            let end = arguments.last().unwrap_or(command);
+            return Err((end.column + end.text.chars().count(), Problem::Missing(what)));
+        }
+        Ok(())
+    };
+    let points = |n: usize| -> Result<Vec<(f32, f32)>, (usize, Problem)> {
+        count(n * 2, "coordinates")?;
+        let numbers = arguments.iter().map(number).collect::<Result<Vec<f32>, _>>()?;
+        Ok(numbers.chunks(2).map(|xy| (xy[0], xy[1])).collect())
+    };
+    let instruction = match command.text.as_str() {
+        "path" => count(0, "").map(|_| Instruction::Path)?,
+        "point" => Instruction::Node(Node::line(points(1)?[0])),
+        "quad" => {
+            let p = points(2)?;
+            Instruction::Node(Node { point: p[1], segment: Segment::Quadratic(p[0]) })
+        }
+        "cubic" => {
+            let p = points(3)?;
+            Instruction::Node(Node { point: p[2], segment: Segment::Cubic(p[0], p[1]) })
+        }
+        "hole" => count(0, "").map(|_| Instruction::Hole)?,
+        "fill" => count(0, "").map(|_| Instruction::Fill)?,
+        "close" => count(0, "").map(|_| Instruction::Close)?,
+        "style" => {
+            if arguments.is_empty() {
+                count(1, "settings such as width=0.01")?;
+            }
+            Instruction::Style(arguments.iter().map(setting).collect::<Result<_, _>>()?)
+        }
+        "translate" => {
+            count(2, "an offset")?;
+            Instruction::Translate((number(&arguments[0])?, number(&arguments[1])?))
+        }
+        "offset" => {
+            count(arguments.len().clamp(1, 2), "a distance")?;
+            let corner = arguments.get(1).map(|token| match token.text.as_str() {
+                "miter" => Ok(Corner::Miter),
+                "round" => Ok(Corner::Round),
+                "square" => Ok(Corner::Square),
+                _ => Err((token.column, Problem::Invalid("miter, round or square", token.text.clone()))),
+            });
+            Instruction::Offset(number(&arguments[0])?, corner.transpose()?)
+        }
+        "delete" => count(0, "").map(|_| Instruction::Delete)?,
+        "layer" => {
+            count(1, "a layer name")?;
+            Instruction::Layer(arguments[0].text.clone())
+        }
+        "save" => {
+            count(1, "a file name")?;
+            Instruction::Save(PathBuf::from(&arguments[0].text))
+        }
+        name => return Err((command.column, Problem::UnknownCommand(name.to_owned()))),
+    };
+    Ok(instruction)
+}
+
+fn number(token: &Token) -> Result<f32, (usize, Problem)> {
+    token.text.parse::<f32>().ok().filter(|n| n.is_finite())
+        .ok_or_else(|| (token.column, Problem::Invalid("a number", token.text.clone())))
+}
+
+fn setting(token: &Token) -> Result<Setting, (usize, Problem)> {
+    let Some((key, value)) = token.text.split_once('=') else {
+        return Err((token.column, Problem::Invalid("a setting such as width=0.01", token.text.clone())));
+    };
+    // 错误指向等号后面的值
+    let value = Token { text: value.to_owned(), column: token.column + key.chars().count() + 1 };
+    let invalid = |what| (value.column, Problem::Invalid(what, value.text.clone()));
+    let color = || Color::parse(&value.text).ok_or_else(|| invalid("a colour"));
+    let setting = match key {
+        "width" => Setting::Width(number(&value)?.max(0.0)),
+        "miter" => Setting::MiterLimit(number(&value)?.max(1.0)),
+        "opacity" => Setting::Opacity(number(&value)?.clamp(0.0, 1.0)),
+        "stroke" => Setting::Stroke(color()?),
+        "fill" => Setting::Fill(color()?),
+        "join" => Setting::Join(match value.text.as_str() {
+            "miter" => Join::Miter,
+            "round" => Join::Round,
+            "bevel" => Join::Bevel,
+            _ => return Err(invalid("miter, round or bevel")),
+        }),
+        "cap" => Setting::Cap(match value.text.as_str() {
+            "butt" => Cap::Butt,
+            "square" => Cap::Square,
+            "round" => Cap::Round,
+            _ => return Err(invalid("butt, square or round")),
+        }),
+        key => return Err((token.column, Problem::UnknownSetting(key.to_owned()))),
+    };
+    Ok(setting)
+}
+
+fn execute(instruction: Instruction, document: &mut Document, size: (u32, u32), selected: &mut Option<usize>) -> Result<(), Problem> {
+    let current = document.current_index();
+    let drawing = !document.current().is_empty();
+    let editing = !matches!(instruction, Instruction::Layer(_) | Instruction::Save(_));
+    if editing && !document.is_editable(current) {
+        return Err(Problem::Refused("the active layer is locked or hidden"));
+    }
+    match instruction {
+        Instruction::Path => {
+            document.finish_path(false, false);
+        }
+        Instruction::Node(node) => {
+            // 路径的第一个点前面没有线段
+            let segment = if drawing { node.segment } else { Segment::Line };
+            document.push_node(Node { segment, ..node });
+        }
+        Instruction::Hole => {
+            if !document.new_contour() {
+                return Err(Problem::Refused("a hole needs three points before it"));
+            }
+        }
+        Instruction::Fill | Instruction::Close => {
+            if !document.new_path(instruction == Instruction::Fill) {
+                return Err(Problem::Refused("there is no path to finish"));
+            }
+        }
+        Instruction::Style(settings) => {
+            let mut style = document.current().style;
+            for setting in settings {
+                match setting {
+                    Setting::Width(width) => style.width = width,
+                    Setting::Join(join) => style.join = join,
+                    Setting::Cap(cap) => style.cap = cap,
+                    Setting::MiterLimit(limit) => style.miter_limit = limit,
+                    Setting::Stroke(color) => style.stroke_color = color,
+                    Setting::Fill(color) => style.fill_color = color,
+                    Setting::Opacity(opacity) => style.opacity = opacity,
+                }
+            }
+            document.set_style(current, style);
+        }
+        Instruction::Translate(offset) => {
+            let index = match *selected {
+                Some(index) => index,
+                None if drawing => current,
+                None => document.last_path().ok_or(Problem::Refused("there is no path to move"))?,
+            };
+            document.translate_shape(index, offset);
+        }
+        Instruction::Offset(distance, corner) => {
+            let index = selected.or(document.last_path()).ok_or(Problem::Refused("there is no path to offset"))?;
+            let corner = corner.unwrap_or(match document.shapes()[index].style.join {
+                Join::Miter => Corner::Miter,
+                Join::Round => Corner::Round,
+                Join::Bevel => Corner::Square,
+            });
+            let result = document.offset_shape(index, distance, corner, cli::TOLERANCE)
+                .ok_or(Problem::Refused("the path cannot be changed"))?;
+            // 结果取代了选中的图形
+            if selected.is_some() {
+                *selected = (!result.is_empty()).then_some(result.start);
+            }
+        }
+        Instruction::Delete => document.delete_path(),
+        Instruction::Layer(name) => match document.layers().iter().position(|layer| layer.name == name) {
+            Some(index) => document.set_active_layer(index),
+            None => {
+                document.add_layer(name);
+            }
+        },
+        Instruction::Save(path) => cli::save(document, &path, size).map_err(|e| Problem::Save(format!("cannot save {}: {e}", path.display())))?,
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    UnknownCommand(String),
+    UnknownSetting(String),
+    /// What was expected and what was found instead.
+    Invalid(&'static str, String),
+    Missing(&'static str),
+    UnexpectedArgument(String),
+    UnclosedQuote,
+    /// The command cannot run on the drawing as it is.
+    Refused(&'static str),
+    /// What went wrong writing the file.
+    Save(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::UnknownCommand(name) => write!(f, "unknown command {name}, expected one of {}", COMMANDS.join(", ")),
+            Problem::UnknownSetting(name) => write!(f, "unknown setting {name}, expected width, join, cap, miter, stroke, fill or opacity"),
+            Problem::Invalid(expected, found) => write!(f, "expected {expected}, found {found:?}"),
+            Problem::Missing(what) => write!(f, "missing {what}"),
+            Problem::UnexpectedArgument(argument) => write!(f, "unexpected argument {argument:?}"),
+            Problem::UnclosedQuote => write!(f, "unclosed quote"),
+            Problem::Refused(reason) => write!(f, "{reason}"),
+            Problem::Save(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub column: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.problem)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (100, 100);
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let e = run(source, &mut Document::new(), SIZE).unwrap_err();
+        (e.line, e.column, e.problem.to_string())
+    }
+
+    #[test]
+    fn builds_shapes() {
+        let mut document = Document::new();
+        let source = "
+            # 一个带洞的正方形，再加一条曲线
+            style width=0.05 fill=#ff0000 join=round
+            point 0 0
+            point 2 0
+            point 2 2
+            point 0 2
+            hole
+            point 0.5 0.5
+            point 1.5 0.5
+            point 1 1.5
+            fill
+            point 3 0   # 注释
+            quad 3.5 1 4 0
+            path
+            translate 1 0.5
+        ";
+        run(source, &mut document, SIZE).unwrap();
+        let shapes = document.shapes();
+        assert_eq!(shapes.len(), 3);
+        assert!(shapes[0].fill && shapes[0].closed && shapes[0].contours.len() == 2);
+        assert_eq!(shapes[0].style.fill_color, Color::rgb(1.0, 0.0, 0.0));
+        assert_eq!((shapes[0].style.width, shapes[0].style.join), (0.05, Join::Round));
+        assert!(!shapes[1].fill && !shapes[1].closed);
+        assert_eq!(shapes[1].contours[0][1], Node { point: (5.0, 0.5), segment: Segment::Quadratic((4.5, 1.5)) });
+        // 样式传给后面的路径
+        assert_eq!(shapes[2].style.width, 0.05);
+
+        // 整个脚本一步撤销
+        assert!(document.undo());
+        assert!(document.shapes()[0].is_empty());
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let (line, column, _) = error("point 0 0\n  pint 1 1");
+        assert_eq!((line, column), (2, 3));
+        assert_eq!(error("point 0 x").1, 9);
+        assert_eq!(error("point 0").1, 8);
+        assert_eq!(error("point 0 0 0").1, 11);
+        assert_eq!(error("style width=0.1 join=sharp").1, 22);
+        assert_eq!(error("style colour=red").1, 7);
+        assert_eq!(error("save \"a b").1, 6);
+        let (line, column, message) = error("point 0 0\nfill\nfill");
+        assert_eq!((line, column, message.as_str()), (3, 1, "there is no path to finish"));
+    }
+
+    #[test]
+    fn mistakes_leave_the_drawing_alone() {
+        let mut document = Document::new();
+        assert!(run("point 0 0\npoint 1 1\nbogus", &mut document, SIZE).is_err());
+        assert!(document.current().is_empty());
+        assert!(!document.undo());
+    }
+
+    #[test]
+    fn layers_by_name() {
+        let mut document = Document::new();
+        run("layer Ink\npoint 0 0\npoint 1 0\npath\nlayer \"Layer 1\"\npoint 0 1\npoint 1 1\npath", &mut document, SIZE).unwrap();
+        assert_eq!(document.layers().len(), 2);
+        assert_eq!(document.draw_order(), [1, 2, 0]);
+    }
+
+    #[test]
+    fn offsets() {
+        let mut document = Document::new();
+        run("point 0 0\npoint 2 0\npoint 2 2\npoint 0 2\nfill\noffset 0.5\npoint 3 0\npoint 5 0\npath\noffset -0.5 square", &mut document, SIZE).unwrap();
+        let shapes = document.shapes();
+        assert_eq!(shapes.len(), 3);
+        assert!((shapes[0].area(1e-4) - 9.0).abs() < 1e-3);
+        // 开放的线两侧各扩出 0.5，方头
+        assert!(shapes[1].fill && shapes[1].closed);
+        assert!((shapes[1].area(1e-4) - 3.0).abs() < 1e-3);
+
+        // 选中的图形优先
+        run_on("offset -1.5", &mut document, SIZE, Some(0)).unwrap();
+        assert_eq!(document.shapes().len(), 2);
+        assert_eq!(error("offset").1, 7);
+        assert_eq!(error("offset 1 sharp").1, 10);
+        assert_eq!(error("offset 1").2, "there is no path to offset");
+    }
+}
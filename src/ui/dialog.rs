@@ -2,12 +2,17 @@ use glam::{Mat4, vec3};
 use wgpu::{Buffer, Device, Queue};
 use wgpu::util::DeviceExt;
 
-use crate::ui::utils::{draw_line, scale_x};
+use crate::camera::Camera;
+use crate::geometry::stroke::{stroke, Stroke};
+use crate::geometry::Mesh;
+use crate::document::Color;
+use crate::ui::ui::Vertex;
+use crate::ui::utils::{push_mesh, scale_x};
 
 pub struct State {
-    vertices: Vec<f32>,
+    vertices: Vec<Vertex>,
     vertices_buffer: Buffer,
-    indices: Vec<u16>,
+    indices: Vec<u32>,
     indices_buffer: Buffer,
     gallery: Gallery,
 }
@@ -18,18 +23,20 @@ struct Gallery {
 }
 
 impl State {
-    pub fn update_transform(&self, queue: &Queue, buffer: &Buffer, ratio: f32) {
+    /// Pins the dialog to the top right corner of the screen, whatever the camera looks at.
+    pub fn update_transform(&self, queue: &Queue, buffer: &Buffer, camera: &Camera) {
+        let ratio = camera.ratio();
         let transform = Mat4::from_scale(vec3(0.5 / ratio, 0.5, 1.0));
         let x_offset = 0.5 - 0.5 / ratio;
         let transform = Mat4::from_translation(vec3(0.5 + x_offset, 0.5, 0.0)) * transform;
         queue.write_buffer(buffer, 0, bytemuck::cast_slice(&transform.to_cols_array_2d()));
     }
-    pub fn update_color(&self, queue: &Queue, buffer: &Buffer) {
-        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[252f32 / 256.0, 222.0 / 256.0, 144.0 / 256.0, 1.0]));
+    pub fn update_color(&self, queue: &Queue, buffer: &Buffer, color: Color) {
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&color.to_array()));
     }
     pub fn draw<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
         rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
-        rpass.set_index_buffer(self.indices_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.set_index_buffer(self.indices_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
         rpass.draw_indexed(0 .. self.indices.len() as u32, 0, 0..1);
     }
@@ -37,12 +44,10 @@ impl State {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
 
-        let mut origin = 0;
-
-        origin += draw_line((-0.99, -0.99), (0.99, -0.99), &mut vertices, &mut indices, 0.01, origin);
-        origin += draw_line((0.99, -0.99), (0.99, 0.99), &mut vertices, &mut indices, 0.01, origin);
-        origin += draw_line((0.99, 0.99), (-0.99, 0.99), &mut vertices, &mut indices, 0.01, origin);
-        origin += draw_line((-0.99, 0.99), (-0.99, -0.99), &mut vertices, &mut indices, 0.01, origin);
+        let mut frame = Mesh::default();
+        let style = Stroke { width: 0.02, join: Default::default(), cap: Default::default(), miter_limit: 4.0 };
+        stroke(&[(-0.99, -0.99), (0.99, -0.99), (0.99, 0.99), (-0.99, 0.99)], true, &style, &mut frame);
+        push_mesh(&frame, &mut vertices, &mut indices, 0, [1.0; 4]);
 
         let vertices_buffer = device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -0,0 +1,132 @@
+//! Lines behind the document, closer together the further the view is zoomed in.
+
+use glam::vec2;
+use wgpu::{Buffer, Device};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::geometry::Mesh;
+use crate::gpu::Gpu;
+use crate::ui::ui::Vertex;
+use crate::ui::utils::push_mesh;
+
+/// Smallest distance between two lines in logical pixels.
+const MIN_SPACING: f32 = 16.0;
+/// Every fifth line is drawn stronger.
+const MAJOR: i64 = 5;
+const MINOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.06];
+const MAJOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.14];
+const AXIS_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.3];
+
+/// Distance between lines in world units: 1, 2 or 5 times a power of ten, at least `MIN_SPACING` apart on screen.
+pub fn spacing(camera: &Camera) -> f32 {
+    let min = MIN_SPACING * camera.scale_factor() / camera.pixels_per_unit();
+    let power = 10f32.powf(min.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|step| step * power).find(|&spacing| spacing >= min).unwrap_or(10.0 * power)
+}
+
+pub struct State {
+    visible: bool,
+    /// The view the lines were laid out for.
+    camera: Option<Camera>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    buffer: Buffer,
+    index_buffer: Buffer,
+}
+
+impl State {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            visible: true,
+            camera: None,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            buffer: Self::create_buffer(device, &[], wgpu::BufferUsages::VERTEX),
+            index_buffer: Self::create_buffer(device, &[], wgpu::BufferUsages::INDEX),
+        }
+    }
+    fn create_buffer(device: &Device, contents: &[u8], usage: wgpu::BufferUsages) -> Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Buffer"),
+            contents,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+    /// Lays the lines out again when the view changed.
+    pub fn update(&mut self, app: &Gpu, camera: &Camera) {
+        if !self.visible || self.camera == Some(*camera) {
+            return;
+        }
+        self.camera = Some(*camera);
+        self.layout(camera);
+
+        let vertices: &[u8] = bytemuck::cast_slice(&self.vertices);
+        let indices: &[u8] = bytemuck::cast_slice(&self.indices);
+        if self.buffer.size() < vertices.len() as u64 {
+            self.buffer = Self::create_buffer(app.device, vertices, wgpu::BufferUsages::VERTEX);
+        }
+        if self.index_buffer.size() < indices.len() as u64 {
+            self.index_buffer = Self::create_buffer(app.device, indices, wgpu::BufferUsages::INDEX);
+        }
+        app.queue.write_buffer(&self.buffer, 0, vertices);
+        app.queue.write_buffer(&self.index_buffer, 0, indices);
+    }
+    fn layout(&mut self, camera: &Camera) {
+        let spacing = spacing(camera);
+        // 线宽一个物理像素
+        let half = 0.5 / camera.pixels_per_unit();
+        let (min, max) = camera.visible_bounds();
+        let mut meshes = [Mesh::default(), Mesh::default(), Mesh::default()];
+        let class = |i: i64| if i == 0 { 2 } else if i % MAJOR == 0 { 1 } else { 0 };
+        for i in (min.x / spacing).ceil() as i64..=(max.x / spacing).floor() as i64 {
+            let x = i as f32 * spacing;
+            meshes[class(i)].push_quad([vec2(x - half, min.y), vec2(x + half, min.y), vec2(x + half, max.y), vec2(x - half, max.y)]);
+        }
+        for i in (min.y / spacing).ceil() as i64..=(max.y / spacing).floor() as i64 {
+            let y = i as f32 * spacing;
+            meshes[class(i)].push_quad([vec2(min.x, y - half), vec2(max.x, y - half), vec2(max.x, y + half), vec2(min.x, y + half)]);
+        }
+
+        self.vertices.clear();
+        self.indices.clear();
+        let mut count = 0;
+        for (mesh, color) in meshes.iter().zip([MINOR_COLOR, MAJOR_COLOR, AXIS_COLOR]) {
+            count += push_mesh(mesh, &mut self.vertices, &mut self.indices, count, color);
+        }
+    }
+    pub fn draw<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+        if !self.visible || self.indices.is_empty() {
+            return;
+        }
+        rpass.set_vertex_buffer(0, self.buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spacing_follows_the_zoom() {
+        // 720 像素高时一个单位 360 像素，16 像素约合 0.044
+        let mut camera = Camera::new(1280, 720);
+        let near = |expected: f32, camera: &Camera| (spacing(camera) - expected).abs() < 1e-6;
+        assert!(near(0.05, &camera));
+        // 放大两倍时线距变成 36 像素，还不到换档的时候
+        camera.zoom_at(vec2(0.0, 0.0), 2.0);
+        assert!(near(0.05, &camera));
+        camera.zoom_at(vec2(0.0, 0.0), 2.5);
+        assert!(near(0.01, &camera));
+        camera.set_scale_factor(2.0);
+        assert!(near(0.02, &camera));
+    }
+}
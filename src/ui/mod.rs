@@ -1,48 +1,106 @@
-use app_surface::AppSurface;
-use glam::Mat4;
-use wgpu::{ShaderStages, SurfaceConfiguration};
+use glam::{Mat4, Vec2};
+use wgpu::ShaderStages;
+use crate::camera::Camera;
+use crate::gpu::Gpu;
+use crate::keymap::Keymap;
+use crate::script;
+use crate::document::{Color, Document, Label, Layer, Operation, Shape, Style};
 use crate::uniform::Proxy as Uniform;
 
 
-mod ui;
+#[allow(clippy::module_inception)]
+pub(super) mod ui;
+pub use ui::next_color;
 pub(super) mod text;
 #[allow(unused)]
 mod dialog;
+mod grid;
 mod utils;
 
+/// Size of the cursor and point markers in logical pixels.
+const MARKER_RADIUS: f32 = 4.0;
+
+/// Colours of everything that is not part of the document.
+#[derive(Copy, Clone, Debug)]
+pub struct Theme {
+    pub cursor: Color,
+    pub points: Color,
+    pub highlight: Color,
+    pub frame: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            cursor: Color::rgb(1.0, 0.0, 1.0),
+            points: Color::GREEN,
+            highlight: Color::rgb(1.0, 0.8, 0.0),
+            frame: Color::rgb(252.0 / 256.0, 222.0 / 256.0, 144.0 / 256.0),
+        }
+    }
+}
+
 pub struct State {
     render_pipeline: wgpu::RenderPipeline,
     ui: ui::State,
     transform: Uniform,
     color: Uniform,
     dialog: dialog::State,
-    ratio: f32,
+    grid: grid::State,
 }
 
 impl State {
-    pub fn resize_view(&mut self, app: &AppSurface) {
-        self.ui.text().resize_view(app);
-        self.ratio = app.config.width as f32 / app.config.height as f32;
+    pub fn resize_view(&mut self, app: &Gpu, camera: &Camera) {
+        self.ui.text_mut().resize_view(app, camera.scale_factor());
     }
-    pub fn update(&mut self, app: &AppSurface) {
-        self.ui.check_buffer(&app.device);
+    pub fn update(&mut self, app: &Gpu, camera: &Camera) {
+        self.ui.check_buffer(app.device);
         app.queue.write_buffer(self.ui.buffer(), 0, self.ui.vertices());
-        self.ui.check_index_buffer(&app.device);
+        self.ui.check_index_buffer(app.device);
         app.queue.write_buffer(self.ui.index_buffer(), 0, self.ui.indices());
-        self.ui.update_color(self.color.buffers(0), &app.queue);
-        self.ui.update_transform(self.transform.buffer(0, 0), &app.queue, self.ratio);
-        self.ui.text_mut().process_queued(app);
-        self.dialog.update_transform(&app.queue, self.transform.buffer(1, 0), self.ratio);
-        self.dialog.update_color(&app.queue, self.color.buffer(1, 0));
+        self.grid.update(app, camera);
+        self.ui.update_color(self.color.buffers(0), app.queue);
+        self.ui.update_transform(self.transform.buffer(0, 0), app.queue, camera);
+        self.ui.queue_text(app, camera);
+        self.dialog.update_transform(app.queue, self.transform.buffer(1, 0), camera);
+        self.dialog.update_color(app.queue, self.color.buffer(1, 0), self.ui.theme().frame);
+    }
+    /// Puts the cursor at the world position under a screen position, hiding it outside the window.
+    pub fn update_cursor(&mut self, camera: &Camera, screen: Vec2) {
+        let cursor = camera.contains(screen).then(|| camera.unproject(screen).into());
+        self.ui.set_grid(self.grid.is_visible().then(|| grid::spacing(camera)));
+        self.ui.update_cursor(cursor);
+    }
+    pub fn toggle_grid(&mut self) {
+        self.grid.set_visible(!self.grid.is_visible());
+    }
+    pub fn set_grid_visible(&mut self, visible: bool) {
+        self.grid.set_visible(visible);
+    }
+    /// Keeps new and dragged points at multiples of 15° around the previous point.
+    pub fn set_angle_snap(&mut self, enabled: bool) {
+        self.ui.set_angle_snap(enabled);
+    }
+    /// Keeps the proportions of shapes scaled with the handles of their box.
+    pub fn set_uniform_scale(&mut self, enabled: bool) {
+        self.ui.set_uniform_scale(enabled);
+    }
+    pub fn hide_cursor(&mut self) {
+        self.ui.hide_cursor();
     }
-    pub fn update_cursor(&mut self, config: &SurfaceConfiguration, x: f32, y: f32) {
-        let arg_x = (x / config.width as f32 * 2.0 - 1.0) * self.ratio;
-        let arg_y = 1.0 - y / config.height as f32 * 2.0;
-        self.ui.update_cursor(arg_x, arg_y, self.ratio);
+    pub fn set_help_visible(&mut self, visible: bool) {
+        self.ui.set_help_visible(visible);
+    }
+    /// Hides the cursor and vertex markers, which belong to editing rather than to the drawing.
+    pub fn set_markers_visible(&mut self, visible: bool) {
+        self.ui.set_markers_visible(visible);
+    }
+    /// Shows the bindings of `keymap` in the help.
+    pub fn set_help(&mut self, keymap: &Keymap) {
+        self.ui.text_mut().set_help(text::help(keymap));
     }
     pub fn new_path(&mut self, fill: bool) {
         self.ui.new_path(fill);
-        self.ui.update_points();
     }
     pub fn delete_path(&mut self) {
         self.ui.delete_path();
@@ -50,7 +108,112 @@ impl State {
     pub fn push_point(&mut self) {
         self.ui.push_point();
     }
-    pub fn draw<'a, 'b>(&'a mut self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+    pub fn select(&mut self) -> bool {
+        self.ui.select()
+    }
+    pub fn is_dragging(&self) -> bool {
+        self.ui.is_dragging()
+    }
+    pub fn end_drag(&mut self) -> bool {
+        self.ui.end_drag()
+    }
+    pub fn clear_selection(&mut self) {
+        self.ui.clear_selection();
+    }
+    pub fn toggle_selected(&mut self) -> bool {
+        self.ui.toggle_selected()
+    }
+    pub fn combine(&mut self, operation: Operation) {
+        self.ui.combine(operation);
+    }
+    pub fn add_label(&mut self) {
+        self.ui.add_label();
+    }
+    /// Whether typed characters go into a label or the console rather than running commands.
+    pub fn is_typing(&self) -> bool {
+        self.ui.is_editing() || self.ui.is_console_open()
+    }
+    pub fn open_console(&mut self, line: &str) {
+        self.ui.open_console(line);
+    }
+    pub fn take_console_line(&mut self) -> Option<String> {
+        self.ui.take_console_line()
+    }
+    pub fn console_result(&mut self, result: Result<(), String>) {
+        self.ui.console_result(result);
+    }
+    pub fn run_script(&mut self, source: &str, size: (u32, u32)) -> Result<(), script::Error> {
+        self.ui.run_script(source, size)
+    }
+    pub fn type_char(&mut self, c: char) -> bool {
+        self.ui.type_char(c)
+    }
+    pub fn update_label(&mut self, f: impl FnOnce(&mut Label)) -> bool {
+        self.ui.update_label(f)
+    }
+    pub fn add_control_point(&mut self) {
+        self.ui.add_control_point();
+    }
+    pub fn toggle_arc(&mut self) {
+        self.ui.toggle_arc();
+    }
+    /// Flattens curves finely enough for what the camera magnifies them to, and keeps the markers
+    /// the same size on screen.
+    pub fn set_scale(&mut self, camera: &Camera) {
+        // 误差控制在四分之一像素以内
+        let pixels = camera.pixels_per_unit();
+        self.ui.set_scale(0.25 / pixels, MARKER_RADIUS * camera.scale_factor() / pixels);
+    }
+    pub fn document(&self) -> &Document {
+        self.ui.document()
+    }
+    pub fn set_document(&mut self, document: Document) {
+        self.ui.set_document(document);
+    }
+    pub fn document_mut(&mut self) -> &mut Document {
+        self.ui.document_mut()
+    }
+    pub fn undo(&mut self) {
+        self.ui.undo();
+    }
+    pub fn redo(&mut self) {
+        self.ui.redo();
+    }
+    pub fn new_contour(&mut self) {
+        self.ui.new_contour();
+    }
+    pub fn update_style(&mut self, f: impl FnOnce(&mut Style)) {
+        self.ui.update_style(f);
+    }
+    pub fn toggle_fill_rule(&mut self) {
+        self.ui.toggle_fill_rule();
+    }
+    pub fn insert_shapes(&mut self, shapes: Vec<Shape>) {
+        self.ui.insert_shapes(shapes);
+    }
+    pub fn add_layer(&mut self) {
+        self.ui.add_layer();
+    }
+    pub fn remove_layer(&mut self) {
+        self.ui.remove_layer();
+    }
+    pub fn step_active_layer(&mut self, step: isize) {
+        self.ui.step_active_layer(step);
+    }
+    pub fn move_layer(&mut self, step: isize) {
+        self.ui.move_layer(step);
+    }
+    pub fn update_layer(&mut self, f: impl FnOnce(&mut Layer)) {
+        self.ui.update_layer(f);
+    }
+    /// Draws the grid, before anything else so that it stays behind.
+    pub fn draw_grid<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+        rpass.set_pipeline(&self.render_pipeline);
+        rpass.set_bind_group(0, self.transform.bind_group(0, 0), &[]);
+        rpass.set_bind_group(1, self.color.bind_group(0, 1), &[]);
+        self.grid.draw(rpass);
+    }
+    pub fn draw<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
         rpass.set_pipeline(&self.render_pipeline);
 
         rpass.set_bind_group(0, self.transform.bind_group(1, 0), &[]);
@@ -60,7 +223,7 @@ impl State {
         rpass.set_bind_group(0, self.transform.bind_group(0, 0), &[]);
         self.ui.draw(rpass, self.color.buffers(0));
     }
-    pub fn new(app: &AppSurface) -> Self {
+    pub fn new(app: &Gpu) -> Self {
 
         let shader_ui = app
             .device
@@ -70,11 +233,11 @@ impl State {
             });
 
 
-        let ratio = 16.0 / 9.0;
-        let ui = ui::State::new(&app);
-        let transform = Uniform::new(bytemuck::cast_slice(&Mat4::IDENTITY.to_cols_array_2d()), &[1, 1],  &app.device, ShaderStages::VERTEX);
-        let color = Uniform::new(bytemuck::cast_slice(&[1.0f32; 4]), &[2, 1], &app.device, ShaderStages::FRAGMENT);
-        let dialog = dialog::State::new(&app.device);
+        let ui = ui::State::new(app);
+        let transform = Uniform::new(bytemuck::cast_slice(&Mat4::IDENTITY.to_cols_array_2d()), &[1, 1],  app.device, ShaderStages::VERTEX);
+        let color = Uniform::new(bytemuck::cast_slice(&[1.0f32; 4]), &[3, 1], app.device, ShaderStages::FRAGMENT);
+        let dialog = dialog::State::new(app.device);
+        let grid = grid::State::new(app.device);
 
         let render_ui_pipeline_layout =
             app.device
@@ -101,10 +264,8 @@ impl State {
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
                         format: app.config.format.add_srgb_suffix(),
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
+                        // 图形可以半透明
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
                 }),
@@ -132,6 +293,6 @@ impl State {
                 multiview: None,
             });
 
-        Self { render_pipeline, ui, transform, color, dialog, ratio }
+        Self { render_pipeline, ui, transform, color, dialog, grid }
     }
 }
\ No newline at end of file
@@ -14,10 +14,12 @@ var<uniform> transform: TransformUniform;
 
 struct VertexInput {
     @location(0) position: vec2f,
+    @location(1) color: vec4f,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4f,
+    @location(0) color: vec4f,
 };
 
 @vertex
@@ -26,6 +28,7 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.clip_position = transform.transform * vec4f(model.position, 0.0, 1.0);
+    out.color = model.color;
     return out;
 }
 
@@ -33,5 +36,6 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
-    return color.color;
+    // 顶点颜色再乘上统一的色调
+    return in.color * color.color;
 }
@@ -1,31 +1,245 @@
-use wgpu_text::{glyph_brush::{Section as TextSection, Text}, BrushBuilder, TextBrush};
-use glyph_brush::ab_glyph::FontRef;
+use glam::{vec2, vec3, Mat4, Vec2};
+use wgpu_text::{glyph_brush::{HorizontalAlign, Layout, Section as TextSection, Text, VerticalAlign}, BrushBuilder, TextBrush};
+use glyph_brush::ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+
+use crate::camera::Camera;
+use crate::command::Command;
+use crate::document::{Align, Document, Label};
+use crate::keymap::{format_binding, Keymap};
+
+/// What the mouse does, which is not in the keymap.
+const MOUSE_HELP: &str = "drag points or shapes to move them, Shift+click to select several shapes, the handles around a selected shape to scale it (Shift keeps its proportions) or turn it around its centre point, hold Ctrl to snap to 15° angles, Enter to edit the selected label, the mouse wheel to zoom";
+/// Height of the help text in logical pixels.
+const FONT_SIZE: f32 = 16.0;
+/// Labels larger than this many pixels on screen are left out, their glyphs would not fit the glyph cache.
+const MAX_LABEL_PIXELS: f32 = 1024.0;
 
 pub struct State {
-    brush: TextBrush<FontRef<'static>>,
-    section: TextSection<'static>,
+    brush: TextBrush<FontArc>,
+    help: String,
+    /// The console line and the error above it, at the bottom of the window.
+    console: Option<String>,
+    /// The layers of the document, at the bottom right of the window.
+    layers: String,
+    /// How far the shape being dragged was moved, scaled or turned, at the bottom of the window.
+    feedback: Option<String>,
+    scale_factor: f32,
+    width: f32,
+    height: f32,
+    /// Draws the labels of the document, in world coordinates.
+    labels: TextBrush<FontArc>,
+    font: FontArc,
 }
 
-impl State {
-    pub fn new(app: &app_surface::AppSurface) -> Self {
-        let text = "Press Z to start path, F to fill and start path, D to delete path";
+/// Lists the first binding of each command; commands with the same description share an entry.
+pub fn help(keymap: &Keymap) -> String {
+    let mut entries: Vec<(&str, Vec<String>)> = Vec::new();
+    for command in Command::all() {
+        let Some(binding) = keymap.binding(command) else {
+            continue;
+        };
+        let binding = format_binding(binding);
+        match entries.iter_mut().find(|(description, _)| *description == command.description()) {
+            Some((_, bindings)) => bindings.push(binding),
+            None => entries.push((command.description(), vec![binding])),
+        }
+    }
+    let entries: Vec<String> = entries.into_iter()
+        .map(|(description, bindings)| format!("{} to {description}", join_bindings(&bindings)))
+        .collect();
+    format!("Press {}, {MOUSE_HELP}", entries.join(", "))
+}
+
+/// One line per layer from the top down, the active one marked with `>`.
+pub fn layers(document: &Document) -> String {
+    let active = document.active_layer();
+    let lines: Vec<String> = document.layers().iter().enumerate().rev()
+        .map(|(i, layer)| {
+            let marker = if i == active { ">" } else { " " };
+            let mut line = format!("{marker} {}", layer.name);
+            if !layer.visible {
+                line.push_str("  hidden");
+            }
+            if layer.locked {
+                line.push_str("  locked");
+            }
+            if layer.opacity < 1.0 {
+                line.push_str(&format!("  {:.0}%", layer.opacity * 100.0));
+            }
+            line
+        })
+        .collect();
+    lines.join("\n")
+}
 
+/// `Ctrl+1/Ctrl+2/Ctrl+3` becomes `Ctrl+1-3`.
+fn join_bindings(bindings: &[String]) -> String {
+    let digit = |binding: &String| {
+        let (prefix, last) = binding.split_at(binding.len() - 1);
+        Some((prefix.to_owned(), last.parse::<u32>().ok()?))
+    };
+    let digits: Option<Vec<(String, u32)>> = bindings.iter().map(digit).collect();
+    if let Some(digits) = digits.filter(|digits| digits.len() > 2) {
+        let (prefix, first) = &digits[0];
+        let consecutive = digits.iter().enumerate().all(|(i, (p, n))| p == prefix && *n == first + i as u32);
+        if consecutive {
+            return format!("{prefix}{first}-{}", digits[digits.len() - 1].1);
+        }
+    }
+    bindings.join("/")
+}
+
+impl State {
+    pub fn new(app: &crate::gpu::Gpu) -> Self {
+        let font = crate::font::shared();
+        let brush = || BrushBuilder::using_font(font.clone())
+            .build(app.device, app.config.width, app.config.height, app.config.format);
         Self {
-            brush: BrushBuilder::using_font_bytes(include_bytes!("C:\\Windows\\Fonts\\msyh.ttc")).unwrap()
-                .build(&app.device, app.config.width, app.config.height, app.config.format),
-            section: TextSection::default().add_text(Text::new(text)),
+            brush: brush(),
+            help: String::new(),
+            console: None,
+            layers: String::new(),
+            feedback: None,
+            scale_factor: 1.0,
+            width: app.config.width as f32,
+            height: app.config.height as f32,
+            labels: brush(),
+            font: font.clone(),
         }
     }
 
-    pub fn process_queued(&mut self, app: &app_surface::AppSurface) {
-        self.brush.queue(&app.device, &app.queue, vec![&self.section]).unwrap();
+    /// Queues the console and the drag feedback, and the help and the layers when `help_visible`.
+    pub fn process_queued(&mut self, app: &crate::gpu::Gpu, help_visible: bool) {
+        let scale = FONT_SIZE * self.scale_factor;
+        let mut sections = Vec::new();
+        if help_visible {
+            sections.push(TextSection::default().add_text(Text::new(&self.help).with_scale(scale)));
+            sections.push(TextSection::default()
+                .with_screen_position((self.width - scale / 2.0, self.height - scale / 2.0))
+                .with_layout(Layout::default().h_align(HorizontalAlign::Right).v_align(VerticalAlign::Bottom))
+                .add_text(Text::new(&self.layers).with_scale(scale)));
+        }
+        if let Some(feedback) = &self.feedback {
+            sections.push(TextSection::default()
+                .with_screen_position((self.width / 2.0, self.height - scale / 2.0))
+                .with_layout(Layout::default_single_line().h_align(HorizontalAlign::Center).v_align(VerticalAlign::Bottom))
+                .add_text(Text::new(feedback).with_scale(scale).with_color([1.0, 1.0, 1.0, 1.0])));
+        }
+        if let Some(console) = &self.console {
+            sections.push(TextSection::default()
+                .with_screen_position((scale / 2.0, self.height - scale / 2.0))
+                .with_layout(Layout::default().v_align(VerticalAlign::Bottom))
+                .add_text(Text::new(console).with_scale(scale).with_color([1.0, 1.0, 1.0, 1.0])));
+        }
+        self.brush.queue(app.device, app.queue, sections).unwrap();
+    }
+
+    /// The glyph scale of `ab_glyph` for a font size.
+    fn px_scale(&self, size: f32) -> PxScale {
+        let height = self.font.height_unscaled();
+        PxScale::from(size * height / self.font.units_per_em().unwrap_or(height))
+    }
+
+    /// Corners around the text of `label` in world units, from the descender to the ascender.
+    pub fn bounds(&self, label: &Label) -> (Vec2, Vec2) {
+        let font = self.font.as_scaled(self.px_scale(label.size));
+        let mut width = 0.0;
+        let mut previous = None;
+        for c in label.text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                width += font.kern(previous, id);
+            }
+            width += font.h_advance(id);
+            previous = Some(id);
+        }
+        let left = match label.align {
+            Align::Left => 0.0,
+            Align::Center => -width / 2.0,
+            Align::Right => -width,
+        };
+        let (x, y) = label.position;
+        (vec2(x + left, y + font.descent()), vec2(x + left + width, y + font.ascent()))
+    }
+
+    /// Lays out the visible labels for the camera.
+    pub fn queue_labels(&mut self, app: &crate::gpu::Gpu, camera: &Camera, labels: &[Label]) {
+        // 按屏幕像素的大小排字，字形才清晰；再用矩阵换回世界坐标，旋转也一起处理
+        let pixels = camera.pixels_per_unit();
+        let matrix = camera.projection() * Mat4::from_scale(vec3(1.0 / pixels, -1.0 / pixels, 1.0));
+        self.labels.update_matrix(matrix.to_cols_array_2d(), app.queue);
+
+        let (min, max) = camera.visible_bounds();
+        let sections: Vec<TextSection> = labels.iter()
+            .filter(|label| {
+                let (lo, hi) = self.bounds(label);
+                !label.text.is_empty() && label.size * pixels <= MAX_LABEL_PIXELS
+                    && lo.cmple(max).all() && hi.cmpge(min).all()
+            })
+            .map(|label| {
+                let scale = self.px_scale(label.size * pixels);
+                // 排版从行顶开始，位置在基线上
+                let ascent = self.font.as_scaled(scale).ascent();
+                let align = match label.align {
+                    Align::Left => HorizontalAlign::Left,
+                    Align::Center => HorizontalAlign::Center,
+                    Align::Right => HorizontalAlign::Right,
+                };
+                TextSection::default()
+                    .with_screen_position((label.position.0 * pixels, -label.position.1 * pixels - ascent))
+                    .with_layout(Layout::default_single_line().h_align(align))
+                    .add_text(Text::new(&label.text).with_scale(scale).with_color(label.color.to_array()))
+            })
+            .collect();
+        if let Err(e) = self.labels.queue(app.device, app.queue, sections) {
+            eprintln!("Cannot draw labels: {e}");
+        }
     }
 
     pub fn draw<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
         self.brush.draw(rpass);
     }
 
-    pub fn resize_view(&self, app: &app_surface::AppSurface) {
-        self.brush.resize_view(app.config.width as f32, app.config.height as f32, &app.queue);
+    pub fn draw_labels<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>) where 'a: 'b {
+        self.labels.draw(rpass);
+    }
+
+    pub fn resize_view(&mut self, app: &crate::gpu::Gpu, scale_factor: f32) {
+        self.brush.resize_view(app.config.width as f32, app.config.height as f32, app.queue);
+        self.scale_factor = scale_factor;
+        self.width = app.config.width as f32;
+        self.height = app.config.height as f32;
+    }
+    pub fn set_console(&mut self, console: Option<String>) {
+        self.console = console;
+    }
+    pub fn set_help(&mut self, help: String) {
+        self.help = help;
+    }
+    pub fn set_layers(&mut self, layers: String) {
+        self.layers = layers;
+    }
+    pub fn set_feedback(&mut self, feedback: Option<String>) {
+        self.feedback = feedback;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_follows_the_keymap() {
+        let mut keymap = Keymap::default();
+        let text = help(&keymap);
+        assert!(text.starts_with("Press Z to start path, F to fill and start path"), "{text}");
+        assert!(text.contains(", [/] to change width or label size, ") && text.contains(", +/- to zoom, "), "{text}");
+        assert!(text.contains(", Ctrl+1-9 to save the view, 1-9 to go back to it, "), "{text}");
+
+        keymap.rebind(Command::NewPath, vec![crate::keymap::parse_binding("Ctrl+K N").unwrap()]);
+        keymap.rebind(Command::View(5), Vec::new());
+        let text = help(&keymap);
+        assert!(text.starts_with("Press Ctrl+K N to start path, "), "{text}");
+        assert!(text.contains(", 1/2/3/4/6/7/8/9 to go back to it, "), "{text}");
     }
-}
\ No newline at end of file
+}
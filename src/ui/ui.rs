@@ -1,19 +1,32 @@
-use std::ops::Index;
-use app_surface::AppSurface;
-use glam::{Mat4, vec3};
+use std::borrow::Cow;
+
+use glam::{Affine2, Vec2};
+
+use crate::camera::Camera;
+use crate::gpu::Gpu;
 use wgpu::{Buffer, Device, Queue};
 use wgpu::util::DeviceExt;
+use crate::document::hit::{hit_test, Hit, Target};
+use crate::document::snap::{snap, Options, Snap};
+use crate::document::transform::{describe, Frame, Handle};
+use crate::document::{Color, Document, Label, Layer, Node, Operation, Segment, Shape, Style};
 use crate::uniform::BufferAndBindGroupBindingVec;
+use crate::script;
 
 use super::text;
+use super::Theme;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     position: [f32; 2],
+    color: [f32; 4],
 }
 
 impl Vertex {
+    pub fn new(x: f32, y: f32, color: [f32; 4]) -> Self {
+        Self { position: [x, y], color }
+    }
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -24,96 +37,164 @@ impl Vertex {
                     shader_location: 0,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
-struct Shape {
-    shape: Vec<(f32, f32)>,
-    fill: bool,
+/// Size of the cursor and point markers in world units until `set_scale` is called.
+const RADIUS: f32 = 0.01;
+const WHITE: [f32; 4] = [1.0; 4];
+/// How far the rotation handle sits above the box of the selected shape, in marker radii.
+const ROTATE_HANDLE: f32 = 6.0;
+
+/// Colours offered when cycling the fill or stroke colour.
+pub const PALETTE: [Color; 8] = [
+    Color::GREEN,
+    Color::rgb(1.0, 1.0, 1.0),
+    Color::rgb(0.0, 0.0, 0.0),
+    Color::rgb(0.9, 0.2, 0.2),
+    Color::rgb(1.0, 0.6, 0.0),
+    Color::rgb(1.0, 0.9, 0.2),
+    Color::rgb(0.2, 0.5, 1.0),
+    Color::rgb(0.6, 0.3, 0.9),
+];
+
+/// The palette colour after `color`, keeping its alpha.
+pub fn next_color(color: Color) -> Color {
+    let opaque = color.with_alpha(1.0);
+    let next = PALETTE.iter().position(|&c| c == opaque).map_or(0, |i| (i + 1) % PALETTE.len());
+    PALETTE[next].with_alpha(color.a)
 }
 
-impl Shape {
-    fn push(&mut self, p: (f32, f32)) {
-        self.shape.push(p)
-    }
-    fn is_empty(&self) -> bool {
-        self.shape.is_empty()
-    }
-    fn len(&self) -> usize {
-        self.shape.len()
-    }
-    fn last(&self) -> Option<&(f32, f32)> {
-        self.shape.last()
-    }
-    fn truncate(&mut self, offset: usize) {
-        self.shape.truncate(offset)
-    }
+/// A selected shape, or one of its vertices as `(contour, node)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Selection {
+    shape: usize,
+    vertex: Option<(usize, usize)>,
 }
 
-impl<'a> IntoIterator for &'a Shape {
-    type Item = &'a (f32, f32);
-    type IntoIter = std::slice::Iter<'a, (f32, f32)>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.shape[..].into_iter()
-    }
+/// A handle of the selected shape being dragged from `from`, and what the drag does so far.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Transform {
+    handle: Handle,
+    from: (f32, f32),
+    transform: Affine2,
 }
 
-impl Index<usize> for Shape {
-    type Output = (f32, f32);
-
-    fn index(&self, index: usize) -> &Self::Output {
-        self.shape.index(index)
+/// The shape at `index` as drawn while `moving` shows how a shape is being transformed.
+fn preview(document: &Document, moving: Option<(usize, Affine2)>, index: usize) -> Cow<'_, Shape> {
+    let shape = &document.shapes()[index];
+    match moving {
+        Some((moved, transform)) if moved == index => {
+            let mut shape = shape.clone();
+            shape.transform(transform);
+            Cow::Owned(shape)
+        }
+        _ => Cow::Borrowed(shape),
     }
 }
 
 pub struct State {
     cursor: Option<(f32, f32)>,
-    points: Vec<Shape>,
-    vertices: Vec<f32>,
-    indices: Vec<u16>,
+    /// Where the cursor is pulled to, shown in place of the cursor.
+    snap: Option<Snap>,
+    /// Spacing of the grid to snap to.
+    grid: Option<f32>,
+    angle_snap: bool,
+    uniform_scale: bool,
+    selection: Option<Selection>,
+    /// Shapes selected with Shift besides the selected one, combined with it by boolean operations.
+    others: Vec<usize>,
+    /// Applied to the selected shape when the drag ends, until then it is only drawn that way.
+    transform: Option<Transform>,
+    /// Where the selected shape turns around once its pivot was dragged away from the centre.
+    pivot: Option<Vec2>,
+    /// Selected label, never together with a selected shape.
+    label: Option<usize>,
+    /// Whether typed characters go into the selected label.
+    editing: bool,
+    /// The script command being typed, while the console is open.
+    console: Option<String>,
+    /// What went wrong with the last command run from the console.
+    console_error: Option<String>,
+    /// A command entered in the console, waiting to be run.
+    submitted: Option<String>,
+    dragging: bool,
+    /// First index drawn in the highlight colour.
+    highlight: u32,
+    document: Document,
+    /// Segment used to reach the next point, collected with `add_control_point` and `toggle_arc`.
+    pending: Option<Segment>,
+    tolerance: f32,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    theme: Theme,
     radius: f32,
     segments_count: usize,
     buffer: Buffer,
     index_buffer: Buffer,
     text: text::State,
+    show_help: bool,
+    /// Whether the cursor and the vertices of editable shapes are marked.
+    show_markers: bool,
 }
 
 impl State {
     pub fn draw<'a, 'b>(&'a self, rpass: &mut wgpu::RenderPass<'b>, color_buffers: &'a BufferAndBindGroupBindingVec) where 'a: 'b {
 
         rpass.set_vertex_buffer(0, self.buffer.slice(..));
-        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
-        let index_buffer_len = self.index_buffer.size() as u32 / 2;
+        let index_buffer_len = self.index_buffer.size() as u32 / 4;
         let indices_len = self.indices.len() as u32;
 
         if index_buffer_len > 0 {
             let count_cursor = self.segments_count as u32 * 3;
 
-            if self.cursor.is_some() {
+            if self.cursor.is_some() && self.show_markers {
                 rpass.set_bind_group(1, color_buffers.bind_group(0), &[]);
 
                 rpass.draw_indexed(0..count_cursor, 0, 0..1);
             }
 
-            rpass.set_bind_group(1, color_buffers.bind_group(1), &[]);
+            let end = indices_len.min(index_buffer_len);
+            let highlight = self.highlight.clamp(count_cursor, end);
 
+            rpass.set_bind_group(1, color_buffers.bind_group(1), &[]);
+            rpass.draw_indexed(count_cursor..highlight, 0, 0..1);
 
-            rpass.draw_indexed(count_cursor..indices_len.min(index_buffer_len), 0, 0..1);
+            rpass.set_bind_group(1, color_buffers.bind_group(2), &[]);
+            rpass.draw_indexed(highlight..end, 0, 0..1);
         }
 
+        self.text.draw_labels(rpass);
         self.text.draw(rpass);
     }
+    pub fn set_help_visible(&mut self, visible: bool) {
+        self.show_help = visible;
+    }
+    pub fn set_markers_visible(&mut self, visible: bool) {
+        self.show_markers = visible;
+        self.update_points();
+    }
+    /// Writes the tints of the cursor, the shapes and the selection; shapes carry their own colours
+    /// in their vertices so their tint stays white.
     pub fn update_color(&self, buffers: &BufferAndBindGroupBindingVec, queue: &Queue) {
-        queue.write_buffer(buffers.buffer(0), 0, bytemuck::cast_slice(&[1f32, 0.0, 1.0, 1.0]));
-        queue.write_buffer(buffers.buffer(1), 0, bytemuck::cast_slice(&[0f32, 1.0, 0.0, 1.0]));
+        queue.write_buffer(buffers.buffer(0), 0, bytemuck::cast_slice(&self.theme.cursor.to_array()));
+        queue.write_buffer(buffers.buffer(1), 0, bytemuck::cast_slice(&Color::WHITE.to_array()));
+        queue.write_buffer(buffers.buffer(2), 0, bytemuck::cast_slice(&self.theme.highlight.to_array()));
     }
-    pub fn update_transform(&self, buffer: &Buffer, queue: &Queue, ratio: f32) {
-        let transform = Mat4::from_scale(vec3(1.0 / ratio, 1.0, 1.0));
-        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&transform.to_cols_array_2d()));
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+    pub fn update_transform(&self, buffer: &Buffer, queue: &Queue, camera: &Camera) {
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&camera.projection().to_cols_array_2d()));
     }
     pub fn vertices(&self) -> &[u8] {
         bytemuck::cast_slice(&self.vertices)
@@ -131,7 +212,7 @@ impl State {
     }
 
     pub fn check_buffer(&mut self, device: &Device) {
-        if self.buffer.size() >= self.vertices().len() as u64 * 4 {
+        if self.buffer.size() >= self.vertices().len() as u64 {
             return
         }
 
@@ -144,7 +225,7 @@ impl State {
         self.buffer = cursor_buffer;
     }
     pub fn check_index_buffer(&mut self, device: &Device) {
-        if self.index_buffer.size() >= self.indices().len() as u64 * 2 {
+        if self.index_buffer.size() >= self.indices().len() as u64 {
             return
         }
 
@@ -156,92 +237,573 @@ impl State {
             });
         self.index_buffer = index_buffer;
     }
-    pub fn text(&self) -> &text::State {
-        &self.text
-    }
     pub fn text_mut(&mut self) -> &mut text::State {
         &mut self.text
     }
+    pub fn queue_text(&mut self, app: &Gpu, camera: &Camera) {
+        self.text.process_queued(app, self.show_help);
+        self.text.queue_labels(app, camera, self.document.labels());
+    }
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+    pub fn set_document(&mut self, mut document: Document) {
+        document.history_mut().set_depth(self.document.history().depth());
+        self.document = document;
+        self.update_points();
+    }
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+    pub fn undo(&mut self) {
+        self.finish_editing();
+        if self.document.undo() {
+            self.update_points();
+        }
+    }
+    pub fn redo(&mut self) {
+        self.finish_editing();
+        if self.document.redo() {
+            self.update_points();
+        }
+    }
+    pub fn new_contour(&mut self) {
+        if self.document.new_contour() {
+            self.update_points();
+        }
+    }
+    /// Changes the style of the selected shape, or of the path being drawn when nothing is selected;
+    /// later paths keep using the style of the path being drawn.
+    pub fn update_style(&mut self, f: impl FnOnce(&mut Style)) {
+        let index = self.selection.map_or(self.document.current_index(), |selection| selection.shape);
+        let mut style = self.document.shapes()[index].style;
+        f(&mut style);
+        self.document.set_style(index, style);
+        self.update_points();
+    }
+    pub fn toggle_fill_rule(&mut self) {
+        self.document.toggle_fill_rule();
+        self.update_points();
+    }
+    pub fn insert_shapes(&mut self, shapes: Vec<Shape>) {
+        self.document.insert_shapes(shapes);
+        self.update_points();
+    }
+    /// Adds a layer above the active one and draws into it.
+    pub fn add_layer(&mut self) {
+        let name = format!("Layer {}", self.document.layers().len() + 1);
+        self.document.add_layer(name);
+        self.update_points();
+    }
+    /// Removes the active layer with its shapes, unless it is the only one.
+    pub fn remove_layer(&mut self) {
+        if self.document.remove_layer(self.document.active_layer()) {
+            self.update_points();
+        }
+    }
+    /// Draws into the layer `step` places above the active one, or below for negative steps.
+    pub fn step_active_layer(&mut self, step: isize) {
+        let index = self.document.active_layer().saturating_add_signed(step);
+        if index < self.document.layers().len() {
+            self.document.set_active_layer(index);
+            self.update_points();
+        }
+    }
+    /// Moves the active layer `step` places up, or down for negative steps.
+    pub fn move_layer(&mut self, step: isize) {
+        let index = self.document.active_layer();
+        self.document.move_layer(index, index.saturating_add_signed(step));
+        self.update_points();
+    }
+    /// Changes the active layer; locked and hidden layers can still be changed this way.
+    pub fn update_layer(&mut self, f: impl FnOnce(&mut Layer)) {
+        let index = self.document.active_layer();
+        let mut layer = self.document.layers()[index].clone();
+        f(&mut layer);
+        self.document.set_layer(index, layer);
+        self.update_points();
+    }
+    /// Where a point placed now would go.
+    fn target(&self) -> Option<(f32, f32)> {
+        self.snap.map(|snap| snap.point).or(self.cursor)
+    }
+    pub fn set_grid(&mut self, spacing: Option<f32>) {
+        self.grid = spacing;
+    }
+    pub fn set_angle_snap(&mut self, enabled: bool) {
+        self.angle_snap = enabled;
+    }
+    pub fn set_uniform_scale(&mut self, enabled: bool) {
+        self.uniform_scale = enabled;
+    }
+    /// The box around the selected shape, when a whole shape is selected.
+    fn frame(&self) -> Option<Frame> {
+        let selection = self.selection.filter(|selection| selection.vertex.is_none())?;
+        let frame = Frame::new(self.document.shapes()[selection.shape].bounds(self.tolerance)?);
+        Some(Frame { pivot: self.pivot.unwrap_or(frame.pivot), ..frame })
+    }
+    /// The selected shape and how it is being transformed, while a handle is dragged.
+    fn moving(&self) -> Option<(usize, Affine2)> {
+        let selection = self.selection?;
+        self.transform.filter(|transform| transform.transform != Affine2::IDENTITY)
+            .map(|transform| (selection.shape, transform.transform))
+    }
+    /// Finds where a point placed or dragged to `cursor` should go; shapes dragged as a whole follow
+    /// the mouse freely.
+    fn find_snap(&self, cursor: (f32, f32)) -> Option<Snap> {
+        let dragged = match self.selection {
+            Some(Selection { shape, vertex: Some((contour, node)) }) if self.dragging => Some((shape, contour, node)),
+            _ if self.dragging => return None,
+            _ => None,
+        };
+        // 角度以前一个点为准：拖动时是轮廓里的上一个点，画图时是最后画的点
+        let previous = match dragged {
+            Some((shape, contour, node)) => {
+                let shape = &self.document.shapes()[shape];
+                let nodes = &shape.contours[contour];
+                (nodes.len() > 1 && (node > 0 || shape.is_contour_closed(contour)))
+                    .then(|| nodes[(node + nodes.len() - 1) % nodes.len()].point)
+            }
+            None => self.document.current().contours.last().and_then(|contour| contour.last()).map(|node| node.point),
+        };
+        let options = Options {
+            radius: self.radius * 2.0,
+            tolerance: self.tolerance,
+            grid: self.grid,
+            angle_from: previous.filter(|_| self.angle_snap),
+            ignore: dragged,
+        };
+        snap(self.document.shapes(), &self.document.draw_order(), cursor, &options)
+    }
     pub fn push_point(&mut self) {
-        if let Some(cursor) = self.cursor {
-            self.points.last_mut().unwrap().push(cursor);
+        let Some(cursor) = self.target() else {
+            return;
+        };
+        let previous = self.document.current().contours.last().and_then(|contour| contour.last());
+        let segment = match (self.pending.take(), previous) {
+            // 第一个点之前没有线段，控制点作废
+            (_, None) => Segment::Line,
+            (Some(Segment::Arc { sweep, .. }), Some(previous)) => {
+                let radius = glam::Vec2::from(previous.point).distance(cursor.into()) / 2.0;
+                Segment::Arc { radii: (radius, radius), rotation: 0.0, large_arc: false, sweep }
+            }
+            (segment, _) => segment.unwrap_or_default(),
+        };
+        self.document.push_node(Node { point: cursor, segment });
+        self.update_points();
+    }
+    /// Selects the vertex or shape under the cursor and starts dragging it, returns false when
+    /// nothing was hit and the selection was cleared.
+    ///
+    /// While a path is being drawn only vertices can be picked, so that points can still be placed
+    /// on top of other shapes.
+    pub fn select(&mut self) -> bool {
+        self.finish_editing();
+        // 手柄画在最上面，先检查手柄
+        let handle = self.frame().zip(self.cursor)
+            .and_then(|(frame, cursor)| Some((frame.hit(cursor, self.radius * 1.5, self.radius * ROTATE_HANDLE)?, cursor)));
+        if let Some((handle, from)) = handle {
+            self.transform = Some(Transform { handle, from, transform: Affine2::IDENTITY });
+            self.dragging = true;
+            self.update_points();
+            return true;
+        }
+        let drawing = !self.document.current().is_empty();
+        // 标签画在所有图形之上，先检查标签
+        self.label = self.cursor.filter(|_| !drawing).and_then(|cursor| self.hit_label(cursor));
+        if self.label.is_some() {
+            self.selection = None;
+            self.dragging = true;
             self.update_points();
+            return true;
         }
+        let hit = self.hit_shape().filter(|hit| !drawing || matches!(hit.target, Target::Vertex { .. }));
+        let selection = hit.map(|hit| Selection {
+            shape: hit.shape,
+            vertex: match hit.target {
+                Target::Vertex { contour, node } => Some((contour, node)),
+                _ => None,
+            },
+        });
+        if selection.map(|selection| selection.shape) != self.selection.map(|selection| selection.shape) {
+            self.pivot = None;
+        }
+        self.selection = selection;
+        self.others.clear();
+        self.transform = self.selection.zip(self.cursor)
+            .filter(|(selection, _)| selection.vertex.is_none())
+            .map(|(_, from)| Transform { handle: Handle::Body, from, transform: Affine2::IDENTITY });
+        self.dragging = self.selection.is_some();
+        self.update_points();
+        self.dragging
     }
-    pub fn new_path(&mut self, fill: bool) {
-        if self.points.last().unwrap().is_empty() {
+    /// The vertex or shape under the cursor; shapes of hidden and locked layers cannot be hit.
+    fn hit_shape(&self) -> Option<Hit> {
+        let order: Vec<usize> = self.document.draw_order().into_iter()
+            .filter(|&index| self.document.is_editable(index))
+            .collect();
+        hit_test(self.document.shapes(), &order, self.cursor?, self.radius * 1.5, self.tolerance)
+    }
+    /// Adds the shape under the cursor to the selected shapes or takes it out again, returns false
+    /// when there is no shape or a handle of the selected shape is under the cursor.
+    pub fn toggle_selected(&mut self) -> bool {
+        let on_handle = self.frame().zip(self.cursor)
+            .is_some_and(|(frame, cursor)| frame.hit(cursor, self.radius * 1.5, self.radius * ROTATE_HANDLE).is_some());
+        let Some(shape) = self.hit_shape().map(|hit| hit.shape).filter(|_| !on_handle && self.document.current().is_empty()) else {
+            return false;
+        };
+        self.finish_editing();
+        self.label = None;
+        let whole = |shape| Selection { shape, vertex: None };
+        match self.selection {
+            // 取消主选中的图形时，最后加进来的图形接替它
+            Some(selection) if selection.shape == shape => self.selection = self.others.pop().map(whole),
+            Some(_) if self.others.contains(&shape) => self.others.retain(|&other| other != shape),
+            Some(selection) => {
+                self.others.push(selection.shape);
+                self.selection = Some(whole(shape));
+            }
+            None => self.selection = Some(whole(shape)),
+        }
+        self.pivot = None;
+        self.update_points();
+        true
+    }
+    /// Replaces the selected shapes with what `operation` makes of them and selects the result.
+    pub fn combine(&mut self, operation: Operation) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let indices: Vec<usize> = std::iter::once(selection.shape).chain(self.others.iter().copied()).collect();
+        if let Some(result) = self.document.combine(&indices, operation, self.tolerance) {
+            self.selection = (!result.is_empty()).then_some(Selection { shape: result.start, vertex: None });
+            self.others = result.skip(1).collect();
+            self.pivot = None;
+            self.update_points();
+        }
+    }
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+    /// Stops dragging and applies the transform of the selected shape, returns whether anything was
+    /// being dragged.
+    pub fn end_drag(&mut self) -> bool {
+        if let Some((index, transform)) = self.moving() {
+            self.document.transform_shape(index, transform);
+            // 挪开过的旋转中心跟着图形走
+            self.pivot = self.pivot.map(|pivot| transform.transform_point2(pivot));
+        }
+        let dragging = std::mem::take(&mut self.dragging);
+        if self.transform.take().is_some() {
+            self.update_points();
+        }
+        dragging
+    }
+    pub fn clear_selection(&mut self) {
+        self.finish_editing();
+        self.selection = None;
+        self.others.clear();
+        self.label = None;
+        self.update_points();
+    }
+    /// The topmost label whose text is under `point`.
+    fn hit_label(&self, point: (f32, f32)) -> Option<usize> {
+        let point = glam::Vec2::from(point);
+        self.document.labels().iter().rposition(|label| {
+            let (min, max) = self.text.bounds(label);
+            point.cmpge(min - self.radius).all() && point.cmple(max + self.radius).all()
+        })
+    }
+    /// Starts a label at the cursor and types into it; it takes the look of the selected label.
+    pub fn add_label(&mut self) {
+        let Some(position) = self.target() else {
             return;
+        };
+        self.finish_editing();
+        // 新标签在屏幕上约 24 个逻辑像素大小
+        let template = self.label.map_or_else(|| Label { size: self.radius * 6.0, ..Label::new(position, "") },
+                                              |index| self.document.labels()[index].clone());
+        self.document.history_mut().begin_group();
+        self.label = Some(self.document.add_label(Label { text: String::new(), position, ..template }));
+        self.selection = None;
+        self.editing = true;
+        self.update_points();
+    }
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+    pub fn is_console_open(&self) -> bool {
+        self.console.is_some()
+    }
+    /// Opens the console with `line` already typed.
+    pub fn open_console(&mut self, line: &str) {
+        self.finish_editing();
+        self.console = Some(line.to_owned());
+        self.console_error = None;
+        self.update_console();
+    }
+    /// The command entered in the console, which stays open until its result comes back.
+    pub fn take_console_line(&mut self) -> Option<String> {
+        self.submitted.take()
+    }
+    /// Closes the console after a command ran, or shows why it failed.
+    pub fn console_result(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.console = None,
+            Err(e) => self.console_error = Some(e),
         }
-        self.points.last_mut().unwrap().fill = fill;
-        self.points.push(Shape {
-            shape: Vec::new(),
-            fill: false,
+        self.update_console();
+    }
+    pub fn run_script(&mut self, source: &str, size: (u32, u32)) -> Result<(), script::Error> {
+        self.finish_editing();
+        let result = script::run_on(source, &mut self.document, size, self.selection.map(|selection| selection.shape));
+        self.update_points();
+        result
+    }
+    fn update_console(&mut self) {
+        let text = self.console.as_ref().map(|line| match &self.console_error {
+            Some(e) => format!("{e}\n> {line}_"),
+            None => format!("> {line}_"),
         });
+        self.text.set_console(text);
+    }
+    /// Types `c` into the selected label; Enter starts and finishes editing and Escape finishes it.
+    /// Returns false when the character is not meant for a label.
+    pub fn type_char(&mut self, c: char) -> bool {
+        if let Some(line) = &mut self.console {
+            match c {
+                '\r' | '\n' => self.submitted = Some(line.clone()),
+                '\u{1b}' => self.console = None,
+                '\u{8}' | '\u{7f}' => {
+                    line.pop();
+                }
+                c if c.is_control() => {}
+                c => line.push(c),
+            }
+            self.update_console();
+            return true;
+        }
+        let Some(index) = self.label else {
+            return false;
+        };
+        if !self.editing {
+            if c != '\r' {
+                return false;
+            }
+            // 一次编辑算一步撤销
+            self.document.history_mut().begin_group();
+            self.editing = true;
+            self.update_points();
+            return true;
+        }
+        let mut label = self.document.labels()[index].clone();
+        match c {
+            '\r' | '\n' | '\u{1b}' => {
+                self.finish_editing();
+                return true;
+            }
+            '\u{8}' | '\u{7f}' => {
+                label.text.pop();
+            }
+            c if c.is_control() => return true,
+            c => label.text.push(c),
+        }
+        if label != self.document.labels()[index] {
+            self.document.set_label(index, label);
+            self.update_points();
+        }
+        true
+    }
+    /// Stops typing into the selected label, removing it if it was left empty.
+    fn finish_editing(&mut self) {
+        if !std::mem::take(&mut self.editing) {
+            return;
+        }
+        if let Some(index) = self.label.filter(|&index| self.document.labels()[index].text.is_empty()) {
+            self.document.remove_label(index);
+            self.label = None;
+        }
+        self.document.history_mut().end_group();
+        self.update_points();
+    }
+    /// Changes the selected label, returns false when no label is selected.
+    pub fn update_label(&mut self, f: impl FnOnce(&mut Label)) -> bool {
+        let Some(index) = self.label else {
+            return false;
+        };
+        let mut label = self.document.labels()[index].clone();
+        f(&mut label);
+        self.document.set_label(index, label);
+        self.update_points();
+        true
+    }
+    /// Uses the cursor as a control point of the next segment: the first one makes it a quadratic
+    /// curve, the second one a cubic curve.
+    pub fn add_control_point(&mut self) {
+        let Some(cursor) = self.target() else {
+            return;
+        };
+        self.pending = Some(match self.pending {
+            Some(Segment::Quadratic(c)) => Segment::Cubic(c, cursor),
+            Some(Segment::Cubic(c, _)) => Segment::Cubic(c, cursor),
+            _ => Segment::Quadratic(cursor),
+        });
+        self.update_points();
+    }
+    /// Makes the next segment a half circle, pressing again flips its side.
+    pub fn toggle_arc(&mut self) {
+        let sweep = match self.pending {
+            Some(Segment::Arc { sweep, .. }) => !sweep,
+            _ => false,
+        };
+        self.pending = Some(Segment::Arc { radii: (0.0, 0.0), rotation: 0.0, large_arc: false, sweep });
+        self.update_points();
+    }
+    /// Sets the largest distance between curves and the lines drawn for them and the size of the
+    /// markers, both in world units.
+    pub fn set_scale(&mut self, tolerance: f32, radius: f32) {
+        // 只有变化明显时才重新细分曲线
+        let changed = |new: f32, old: f32| (new / old - 1.0).abs() > 0.1;
+        if changed(tolerance, self.tolerance) || changed(radius, self.radius) {
+            self.tolerance = tolerance;
+            self.radius = radius;
+            self.update_points();
+        }
+    }
+    pub fn new_path(&mut self, fill: bool) {
+        if self.document.new_path(fill) {
+            self.update_points();
+        }
     }
+    /// Deletes the selected label, or else the path being drawn or the last one.
     pub fn delete_path(&mut self) {
-        if self.points.len() == 1 {
-            self.points[0].truncate(0);
-        } else {
-            self.points.pop();
+        self.finish_editing();
+        match self.label.take() {
+            Some(index) => self.document.remove_label(index),
+            None => self.document.delete_path(),
         }
         self.update_points();
     }
 
-    fn fill_buffer_with_a_point(vertices: &mut Vec<f32>, indices: &mut Vec<u16>, origin: u16,
-                                factor: f32, radius: f32, x: f32, y: f32, count_segments: u32)
+    #[allow(clippy::too_many_arguments)]
+    fn fill_buffer_with_a_point(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, origin: u32,
+                                factor: f32, radius: f32, x: f32, y: f32, count_segments: u32, color: [f32; 4])
     {
-        vertices.push(x);
-        vertices.push(y);
+        vertices.push(Vertex::new(x, y, color));
         let offset_x = 0.0f32.sin() * radius;
         let offset_y = 0.0f32.cos() * radius;
-        vertices.push(offset_x + x);
-        vertices.push(offset_y + y);
+        vertices.push(Vertex::new(offset_x + x, offset_y + y, color));
         for i in 1 .. count_segments {
             let p = i as f32 * factor;
             indices.push(origin);
-            indices.push(origin + i as u16);
-            indices.push(origin + i as u16 + 1);
+            indices.push(origin + i);
+            indices.push(origin + i + 1);
             let offset_x = p.sin() * radius;
             let offset_y = p.cos() * radius;
-            vertices.push(offset_x + x);
-            vertices.push(offset_y + y);
+            vertices.push(Vertex::new(offset_x + x, offset_y + y, color));
         }
         indices.push(origin);
-        indices.push(origin + count_segments as u16);
+        indices.push(origin + count_segments);
         indices.push(origin + 1);
     }
 
-    pub fn update_cursor(&mut self, x: f32, y: f32, ratio: f32)  {
-        if x.abs() > 2.0 / ratio {
+    /// Moves the cursor to a world position, `None` hides it.
+    pub fn update_cursor(&mut self, cursor: Option<(f32, f32)>)  {
+        let Some(cursor) = cursor else {
             self.cursor = None;
+            self.snap = None;
             return;
-        }
-        self.cursor = Some((x, y));
+        };
+        let previous = self.cursor.replace(cursor);
+        self.snap = self.find_snap(cursor);
 
         let count_segments = self.segments_count as _;
-        let radius = self.radius;
+        // 吸附时光标放大并跳到吸附点上，点击之前就能看到点会落在哪里
+        let ((x, y), radius) = match self.snap {
+            Some(snap) => (snap.point, self.radius * 1.5),
+            None => (cursor, self.radius),
+        };
 
         if self.indices.is_empty() {
             Self::fill_buffer_with_a_point(&mut self.vertices, &mut self.indices, 0,
-                                           2.0 * std::f32::consts::PI / count_segments as f32, radius, x, y, count_segments);
+                                           2.0 * std::f32::consts::PI / count_segments as f32, radius, x, y, count_segments, WHITE);
 
         } else {
 
             let mut vertices = Vec::new();
             let mut indices = Vec::new();
             Self::fill_buffer_with_a_point(&mut vertices, &mut indices, 0,
-                                           2.0 * std::f32::consts::PI / count_segments as f32, radius, x, y, count_segments);
+                                           2.0 * std::f32::consts::PI / count_segments as f32, radius, x, y, count_segments, WHITE);
             self.vertices[..vertices.len()].copy_from_slice(&vertices);
             self.indices[..indices.len()].copy_from_slice(&indices);
         }
 
-        if self.indices.len() % 2 == 1 {
-            self.indices.push(0);
+        if let (true, Some(index), Some((px, py))) = (self.dragging, self.label, previous) {
+            self.document.translate_label(index, (cursor.0 - px, cursor.1 - py));
+            self.update_points();
+        }
+        if let (true, Some(selection), Some(_)) = (self.dragging, self.selection, previous) {
+            match selection.vertex {
+                Some((contour, node)) => {
+                    let (vx, vy) = self.document.shapes()[selection.shape].contours[contour][node].point;
+                    self.document.move_vertex(selection.shape, contour, node, (x - vx, y - vy));
+                }
+                None => if let Some((transform, frame)) = self.transform.zip(self.frame()) {
+                    match transform.handle {
+                        Handle::Pivot => self.pivot = Some(cursor.into()),
+                        handle => {
+                            let moved = frame.drag(handle, transform.from, cursor, self.uniform_scale, self.angle_snap);
+                            self.transform = Some(Transform { transform: moved, ..transform });
+                        }
+                    }
+                }
+            }
+            self.update_points();
         }
     }
 
+    /// Stops drawing the cursor until it moves again, e.g. when there is no window.
+    pub fn hide_cursor(&mut self) {
+        // 光标顶点总在最前面，先占好位置
+        if self.indices.is_empty() {
+            self.update_cursor(Some((0.0, 0.0)));
+        }
+        self.cursor = None;
+        self.snap = None;
+        self.update_points();
+    }
+
     pub fn update_points(&mut self)  {
-        use super::utils::draw_line;
+        use super::utils::push_mesh;
+        use crate::geometry::fill::{overlay, FillRule};
+        use crate::geometry::stroke::{outline, stroke, Cap, Join, Stroke};
+        use crate::geometry::Mesh;
+
+        // 撤销、删除等操作之后选中的对象可能已经不存在，图层也可能被隐藏或锁定
+        let document = &self.document;
+        self.selection = self.selection.filter(|selection| {
+            document.shapes().get(selection.shape).is_some_and(|shape| {
+                !shape.is_empty() && document.is_editable(selection.shape) && selection.vertex.is_none_or(|(contour, node)| {
+                    shape.contours.get(contour).is_some_and(|nodes| node < nodes.len())
+                })
+            })
+        });
+        let primary = self.selection.map(|selection| selection.shape);
+        self.others.retain(|&index| {
+            primary.is_some_and(|primary| primary != index)
+                && index < document.current_index() && document.is_editable(index) && !document.shapes()[index].is_empty()
+        });
+        self.others.dedup();
+        self.label = self.label.filter(|&index| index < self.document.labels().len());
+        self.dragging &= self.selection.is_some() || self.label.is_some();
+        if self.selection.is_none() {
+            self.transform = None;
+            self.pivot = None;
+        }
+        let feedback = self.transform.filter(|_| self.dragging).zip(self.frame())
+            .map(|(transform, frame)| describe(transform.handle, transform.transform, frame.pivot));
+        self.text.set_feedback(feedback);
+        self.text.set_layers(text::layers(&self.document));
 
         if self.indices.is_empty() {
             return;
@@ -250,71 +812,145 @@ impl State {
         let segments_count = self.segments_count as _;
         let radius = self.radius;
 
-        let mut count = segments_count as u16 + 1;
+        let mut count = segments_count + 1;
 
-        self.vertices.truncate(self.segments_count * 2 + 2);
+        self.vertices.truncate(self.segments_count + 1);
         self.indices.truncate(self.segments_count * 3);
 
-        for segment in &self.points {
-            for &(x, y) in segment {
-                Self::fill_buffer_with_a_point(&mut self.vertices, &mut self.indices, count,
-                                               2.0 * std::f32::consts::PI / segments_count as f32, radius, x, y, segments_count);
-                count += segments_count as u16 + 1;
+        // 每个图形先填充后描边，按图层顺序叠放，同一图层里按文档顺序
+        let order = self.document.draw_order();
+        let moving = self.moving();
+        for group in order.chunk_by(|&a, &b| self.document.layer_of(a) == self.document.layer_of(b)) {
+            let opacity = self.document.layers()[self.document.layer_of(group[0])].opacity;
+            let painted: Vec<_> = group.iter()
+                .map(|&index| preview(&self.document, moving, index))
+                .filter(|shape| !shape.is_empty())
+                .map(|shape| {
+                    let contours = shape.flatten(self.tolerance);
+                    let mut outlines = Vec::new();
+                    for (i, contour) in contours.iter().enumerate() {
+                        outline(contour, shape.is_contour_closed(i), &shape.style.stroke(), &mut outlines);
+                    }
+                    let fill = if shape.fill { contours } else { Vec::new() };
+                    (fill, shape.fill_rule, outlines, shape.style)
+                })
+                .collect();
+            // 图形和半透明的图层都像 SVG 的组一样先叠成一体再变淡，重叠的地方只画一次叠好的颜色；
+            // 不透明的图层逐个图形叠放效果相同
+            let batches: Vec<&[_]> = if opacity < 1.0 { vec![&painted[..]] } else { painted.chunks(1).collect() };
+            for batch in batches {
+                let operands = batch.iter()
+                    .flat_map(|(fill, rule, outlines, _)| [(&fill[..], *rule), (&outlines[..], FillRule::NonZero)]);
+                for (covering, mesh) in overlay(operands) {
+                    // 操作数依次是每个图形的填充和描边
+                    let color = covering.chunk_by(|a, b| a / 2 == b / 2).fold(Color::TRANSPARENT, |below, parts| {
+                        let style = batch[parts[0] / 2].3;
+                        style.paint(parts[0] % 2 == 0, parts[parts.len() - 1] % 2 == 1).over(below)
+                    });
+                    count += push_mesh(&mesh, &mut self.vertices, &mut self.indices, count,
+                                       color.with_alpha(color.a * opacity).to_array());
+                }
             }
         }
 
+        let controls = match self.pending.filter(|_| self.show_markers) {
+            Some(Segment::Quadratic(c)) => vec![c],
+            Some(Segment::Cubic(c1, c2)) => vec![c1, c2],
+            _ => Vec::new(),
+        };
+        let points: Vec<(f32, f32)> = order.iter()
+            .filter(|&&index| self.show_markers && self.document.is_editable(index))
+            .flat_map(|&index| preview(&self.document, moving, index).points().copied().collect::<Vec<_>>())
+            .collect();
+        let color = self.theme.points.to_array();
+        for (x, y) in points.into_iter().chain(controls) {
+            Self::fill_buffer_with_a_point(&mut self.vertices, &mut self.indices, count,
+                                           2.0 * std::f32::consts::PI / segments_count as f32, radius, x, y, segments_count, color);
+            count += segments_count + 1;
+        }
 
-        for (i, segment) in self.points.iter().enumerate() {
-            if segment.is_empty() {
-                break;
+        self.highlight = self.indices.len() as u32;
+        if let Some(index) = self.label {
+            let (min, max) = self.text.bounds(&self.document.labels()[index]);
+            let outline = Stroke { width: radius * 0.3, join: Join::Miter, cap: Cap::Butt, miter_limit: 4.0 };
+            let (min, max) = (min - radius * 0.5, max + radius * 0.5);
+            let mut mesh = Mesh::default();
+            stroke(&[(min.x, min.y), (max.x, min.y), (max.x, max.y), (min.x, max.y)], true, &outline, &mut mesh);
+            if self.editing {
+                // 插入点在文字末尾
+                let x = max.x - radius * 0.5;
+                mesh.push_quad([glam::vec2(x, min.y), glam::vec2(x + radius * 0.4, min.y),
+                                glam::vec2(x + radius * 0.4, max.y), glam::vec2(x, max.y)]);
             }
-            let full_shape = i + 1 < self.points.len();
-            for i in 1 .. segment.len() {
-                let p1 = segment[i - 1];
-                let p2 = segment[i];
-                draw_line(p1, p2, &mut self.vertices, &mut self.indices, self.radius, count);
-
-                count += 4;
+            count += push_mesh(&mesh, &mut self.vertices, &mut self.indices, count, WHITE);
+        }
+        let outline = Stroke { width: radius * 0.6, join: Join::Round, cap: Cap::Butt, miter_limit: 4.0 };
+        for &index in &self.others {
+            let shape = &self.document.shapes()[index];
+            let mut mesh = Mesh::default();
+            for (i, contour) in shape.flatten(self.tolerance).iter().enumerate() {
+                stroke(contour, shape.is_contour_closed(i), &outline, &mut mesh);
             }
-            if segment.fill {
-                for &(x, y) in segment {
-                    self.vertices.push(x);
-                    self.vertices.push(y);
+            count += push_mesh(&mesh, &mut self.vertices, &mut self.indices, count, WHITE);
+        }
+        if let Some(selection) = self.selection {
+            let shape = preview(&self.document, moving, selection.shape);
+            let mut mesh = Mesh::default();
+            for (i, contour) in shape.flatten(self.tolerance).iter().enumerate() {
+                stroke(contour, shape.is_contour_closed(i), &outline, &mut mesh);
+            }
+            count += push_mesh(&mesh, &mut self.vertices, &mut self.indices, count, WHITE);
+            for (contour, nodes) in shape.contours.iter().enumerate() {
+                for (node, &Node { point: (x, y), .. }) in nodes.iter().enumerate() {
+                    let radius = if selection.vertex == Some((contour, node)) { radius * 2.0 } else { radius };
+                    Self::fill_buffer_with_a_point(&mut self.vertices, &mut self.indices, count,
+                                                   2.0 * std::f32::consts::PI / segments_count as f32, radius, x, y, segments_count, WHITE);
+                    count += segments_count + 1;
                 }
-                let limit = segment.len();
-                for i in 0 .. limit {
-                    for j in i + 1 .. limit {
-                        for k in j + 1 .. limit {
-                            self.indices.push(count + i as u16);
-                            self.indices.push(count + j as u16);
-                            self.indices.push(count + k as u16);
-                        }
+            }
+        }
+        if let Some(frame) = self.frame() {
+            // 框和手柄跟着正在拖动的图形一起变
+            let transform = moving.map_or(Affine2::IDENTITY, |(_, transform)| transform);
+            let corners = frame.corners().map(|corner| transform.transform_point2(corner).into());
+            let line = Stroke { width: radius * 0.3, join: Join::Miter, cap: Cap::Butt, miter_limit: 4.0 };
+            let mut mesh = Mesh::default();
+            stroke(&corners, true, &line, &mut mesh);
+            let mut markers = Vec::new();
+            for (handle, position) in frame.handles(radius * ROTATE_HANDLE) {
+                let position = transform.transform_point2(position);
+                match handle {
+                    Handle::Scale(_) => {
+                        let (x, y) = (transform.matrix2.x_axis.normalize_or_zero(), transform.matrix2.y_axis.normalize_or_zero());
+                        let (x, y) = (x * radius, y * radius);
+                        mesh.push_quad([position - x - y, position + x - y, position + x + y, position - x + y]);
                     }
+                    Handle::Rotate => {
+                        let top = transform.transform_point2((frame.max + Vec2::new(frame.min.x, frame.max.y)) / 2.0).into();
+                        stroke(&[top, position.into()], false, &line, &mut mesh);
+                        markers.push((position, radius * 1.2));
+                    }
+                    Handle::Pivot => markers.push((position, radius * 0.8)),
+                    Handle::Body => {}
                 }
-                count += segment.len() as u16;
             }
-            if full_shape {
-                let p1 = *segment.last().unwrap();
-                let p2 = segment[0];
-                draw_line(p1, p2, &mut self.vertices, &mut self.indices, self.radius, count);
-
-                count += 4;
+            count += push_mesh(&mesh, &mut self.vertices, &mut self.indices, count, WHITE);
+            for (position, radius) in markers {
+                Self::fill_buffer_with_a_point(&mut self.vertices, &mut self.indices, count,
+                                               2.0 * std::f32::consts::PI / segments_count as f32, radius, position.x, position.y, segments_count, WHITE);
+                count += segments_count + 1;
             }
         }
-
-        if self.indices.len() % 2 == 1 {
-            self.indices.push(0);
-        }
     }
-    pub fn new(app: &AppSurface) -> Self {
+    pub fn new(app: &Gpu) -> Self {
 
-        let text = text::State::new(&app);
+        let text = text::State::new(app);
 
-        let device = &app.device;
+        let device = app.device;
 
         let cursor = Some((0.0, 0.0));
 
-        let radius = 0.01f32;
+        let radius = RADIUS;
 
         let segments_count = 6;
 
@@ -334,14 +970,34 @@ impl State {
 
         Self {
             cursor,
-            points: vec![Shape { shape: vec![], fill: false }],
+            snap: None,
+            grid: None,
+            angle_snap: false,
+            uniform_scale: false,
+            selection: None,
+            others: Vec::new(),
+            transform: None,
+            pivot: None,
+            label: None,
+            editing: false,
+            console: None,
+            console_error: None,
+            submitted: None,
+            dragging: false,
+            highlight: 0,
+            document: Document::new(),
+            pending: None,
+            tolerance: 0.001,
             vertices: Vec::new(),
             indices: Vec::new(),
+            theme: Theme::default(),
             radius,
             segments_count,
             buffer,
             index_buffer,
             text,
+            show_help: true,
+            show_markers: true,
         }
     }
 }